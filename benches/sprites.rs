@@ -0,0 +1,47 @@
+//! Frame time of 100k textured sprites through `BatchRenderer` and
+//! `InstancedRenderer`, rendered offscreen.
+
+use std::io::Cursor;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use cardless_game_engine::cardless::{headless::HeadlessContext, instanced_renderer::InstancedRenderer, simple2d_renderer::BatchRenderer, texture::Texture};
+use glm::vec2;
+
+const SPRITES: usize = 100_000;
+
+fn positions() -> impl Iterator<Item = glm::Vec2> {
+    (0..SPRITES).map(|i| vec2((i % 400) as f32 / 200. - 1., (i / 400) as f32 / 125. - 1.))
+}
+
+fn sprites(c: &mut Criterion) {
+    let context = HeadlessContext::try_new(800, 600).unwrap();
+    let texture = Texture::try_load(Cursor::new(&include_bytes!("../sample_texture_0.png")[..])).unwrap();
+
+    let mut group = c.benchmark_group("100k sprites");
+
+    let mut br = BatchRenderer::try_new(include_str!("../shaders/simple2d.frag"), include_str!("../shaders/simple2d.vert")).unwrap();
+    br.bind();
+    group.bench_function("batched", |b| b.iter(|| {
+        for pos in positions() {
+            br.push_square_texture(pos, vec2(0.01, 0.01), &texture);
+        }
+        br.flush();
+        context.read_frame()
+    }));
+
+    let mut ir = InstancedRenderer::try_new(include_str!("../shaders/instanced.frag"), include_str!("../shaders/instanced.vert")).unwrap();
+    ir.bind();
+    group.bench_function("instanced", |b| b.iter(|| {
+        for pos in positions() {
+            ir.push_square_texture(pos, vec2(0.01, 0.01), &texture);
+        }
+        ir.flush();
+        context.read_frame()
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, sprites);
+criterion_main!(benches);
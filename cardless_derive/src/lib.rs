@@ -0,0 +1,184 @@
+//! `#[derive(Vertex)]` for `cardless_game_engine`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Field, Fields, Lit, LitInt, Type};
+
+/// Implements `Vertex` with one attribute per field, at consecutive locations
+/// starting from 0. Supported field types are `f32`, `f16`, `i32`, `u32`,
+/// `i16`, `u16`, `i8`, `u8`, `glm::Vec2/3/4`, `glm::IVec2/3/4`,
+/// `glm::UVec2/3/4` and arrays of up to 4 scalars; `u8` components are
+/// normalized, being meant for colours. Packed formats need a hand-written layout.
+///
+/// Fields take options through `#[vertex(...)]`:
+/// - `normalized` feeds integer components to the shader as floats in `0..=1`
+///   (or `-1..=1` for signed ones);
+/// - `location = N` places the field at location `N`, the following fields
+///   continuing from `N + 1`.
+///
+/// ```ignore
+/// #[derive(Vertex)]
+/// #[repr(C)]
+/// struct ColorVertex {
+///     pos: glm::Vec2,
+///     #[vertex(location = 3)]
+///     color: [u8; 4],
+/// }
+/// ```
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+struct Options {
+    normalized: bool,
+    location: Option<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    F32,
+    I32,
+    U32,
+    U8,
+    I8,
+    U16,
+    I16,
+    F16,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new(input.ident.span(), "Vertex can only be derived for structs with named fields")),
+        },
+        _ => return Err(Error::new(input.ident.span(), "Vertex can only be derived for structs")),
+    };
+
+    let module = quote!(::cardless_game_engine::cardless::vertex_attribute);
+    let mut locations: Vec<u32> = Vec::new();
+    let mut attributes = Vec::new();
+
+    for field in fields {
+        let options = parse_options(field)?;
+        let (scalar, size) = classify(&field.ty)?;
+
+        let location = options.location.unwrap_or_else(|| locations.last().map_or(0, |location| location + 1));
+        if locations.contains(&location) {
+            return Err(Error::new(field.span(), format!("location {} is already used by another field", location)));
+        }
+        locations.push(location);
+
+        if options.normalized && (scalar == Scalar::F32 || scalar == Scalar::F16) {
+            return Err(Error::new(field.ty.span(), "only integer fields can be normalized"));
+        }
+        let normalized = options.normalized || scalar == Scalar::U8;
+
+        let attribute_type = match scalar {
+            Scalar::F32 => quote!(F32),
+            Scalar::I32 => quote!(I32),
+            Scalar::U32 => quote!(U32),
+            Scalar::U8 => quote!(U8),
+            Scalar::I8 => quote!(I8),
+            Scalar::U16 => quote!(U16),
+            Scalar::I16 => quote!(I16),
+            Scalar::F16 => quote!(F16),
+        };
+        let ident = &field.ident;
+        attributes.push(quote! {
+            #module::VertexAttribute::new(
+                #module::VertexAttributeType::#attribute_type,
+                #size,
+                #normalized,
+                ::core::mem::size_of::<Self>(),
+                ::core::mem::offset_of!(Self, #ident),
+            ).with_location(#location)
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #module::Vertex for #name #type_generics #where_clause {
+            fn get_attributes_layout() -> ::std::vec::Vec<#module::VertexAttribute> {
+                ::std::vec![#(#attributes),*]
+            }
+        }
+    })
+}
+
+fn parse_options(field: &Field) -> syn::Result<Options> {
+    let mut options = Options { normalized: false, location: None };
+
+    for attribute in field.attrs.iter().filter(|attribute| attribute.path().is_ident("vertex")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("normalized") {
+                options.normalized = true;
+                Ok(())
+            } else if meta.path.is_ident("location") {
+                let location: LitInt = meta.value()?.parse()?;
+                options.location = Some(location.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `normalized` or `location = N`"))
+            }
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn classify(ty: &Type) -> syn::Result<(Scalar, usize)> {
+    let unsupported = || Error::new(ty.span(), "unsupported vertex field type, expected f32, f16, i32, u32, i16, u16, \
+        i8, u8, a glm vector or an array of up to 4 of those scalars");
+
+    match ty {
+        Type::Path(path) => {
+            let ident = path.path.segments.last().ok_or_else(unsupported)?.ident.to_string();
+            let classified = match ident.as_str() {
+                "f32" => (Scalar::F32, 1),
+                "i32" => (Scalar::I32, 1),
+                "u32" => (Scalar::U32, 1),
+                "u8" => (Scalar::U8, 1),
+                "i8" => (Scalar::I8, 1),
+                "u16" => (Scalar::U16, 1),
+                "i16" => (Scalar::I16, 1),
+                // `half::f16` or any other 16-bit float named so
+                "f16" => (Scalar::F16, 1),
+                "Vec2" => (Scalar::F32, 2),
+                "Vec3" => (Scalar::F32, 3),
+                "Vec4" => (Scalar::F32, 4),
+                "IVec2" => (Scalar::I32, 2),
+                "IVec3" => (Scalar::I32, 3),
+                "IVec4" => (Scalar::I32, 4),
+                "UVec2" => (Scalar::U32, 2),
+                "UVec3" => (Scalar::U32, 3),
+                "UVec4" => (Scalar::U32, 4),
+                _ => return Err(unsupported()),
+            };
+            Ok(classified)
+        }
+        Type::Array(array) => {
+            let (scalar, size) = classify(&array.elem)?;
+            let len = match &array.len {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Int(len) => len.base10_parse::<usize>()?,
+                    _ => return Err(unsupported()),
+                },
+                _ => return Err(unsupported()),
+            };
+            if size != 1 || !(1..=4).contains(&len) {
+                return Err(unsupported());
+            }
+            Ok((scalar, len))
+        }
+        _ => Err(unsupported()),
+    }
+}
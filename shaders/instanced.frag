@@ -0,0 +1,12 @@
+#version 330 core
+in vec2 frag_uv;
+in vec4 frag_color;
+flat in int frag_texture;
+
+out vec4 finale_color;
+
+#include "cardless/textures.glsl"
+
+void main() {
+    finale_color = sample_texture(frag_texture, frag_uv) * frag_color;
+}
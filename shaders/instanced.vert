@@ -0,0 +1,22 @@
+#version 330 core
+layout (location = 0) in vec2 vert_corner;
+layout (location = 1) in vec4 inst_transform;
+layout (location = 2) in vec2 inst_translation;
+layout (location = 3) in vec4 inst_uv_rect;
+layout (location = 4) in vec4 inst_color;
+layout (location = 5) in int inst_texture;
+
+out vec2 frag_uv;
+out vec4 frag_color;
+flat out int frag_texture;
+
+uniform mat4 u_view_projection;
+
+void main() {
+    mat2 transform = mat2(inst_transform.xy, inst_transform.zw);
+
+    frag_uv = inst_uv_rect.xy + vert_corner * inst_uv_rect.zw;
+    frag_color = inst_color;
+    frag_texture = inst_texture;
+    gl_Position = u_view_projection * vec4(transform * vert_corner + inst_translation, 0.0, 1.0);
+}
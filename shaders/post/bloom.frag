@@ -0,0 +1,30 @@
+#version 330 core
+#include "cardless/post.glsl"
+
+// Brightness above which pixels start to glow.
+uniform float u_threshold;
+uniform float u_intensity;
+// Blur radius in pixels of the frame.
+uniform float u_radius;
+
+vec3 bright(vec2 uv) {
+    vec3 color = texture(u_frame, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    return color * smoothstep(u_threshold, 1.0, brightness);
+}
+
+void main() {
+    vec3 color = texture(u_frame, frag_uv).rgb;
+    vec2 step = u_radius / 4.0 / vec2(textureSize(u_frame, 0));
+
+    // Single pass 9x9 gaussian over the bright parts, cheap rather than wide.
+    float weights[5] = float[](0.227, 0.194, 0.121, 0.054, 0.016);
+    vec3 glow = vec3(0.0);
+    for (int x = -4; x <= 4; x++) {
+        for (int y = -4; y <= 4; y++) {
+            glow += bright(frag_uv + vec2(x, y) * step) * weights[abs(x)] * weights[abs(y)];
+        }
+    }
+
+    finale_color = vec4(color + glow * u_intensity, 1.0);
+}
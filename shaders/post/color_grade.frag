@@ -0,0 +1,26 @@
+#version 330 core
+#include "cardless/post.glsl"
+
+// Strip of N slices of N x N pixels, red growing to the right within a slice,
+// green downwards and blue from slice to slice.
+uniform sampler2D u_lut;
+uniform float u_intensity;
+
+vec3 grade(vec3 color) {
+    float size = float(textureSize(u_lut, 0).y);
+    float blue = color.b * (size - 1.0);
+    float slice = floor(blue);
+    float next = min(slice + 1.0, size - 1.0);
+
+    float x = (color.r * (size - 1.0) + 0.5) / (size * size);
+    // Textures are uploaded bottom row first.
+    float y = 1.0 - (color.g * (size - 1.0) + 0.5) / size;
+    vec3 low = texture(u_lut, vec2(x + slice / size, y)).rgb;
+    vec3 high = texture(u_lut, vec2(x + next / size, y)).rgb;
+    return mix(low, high, blue - slice);
+}
+
+void main() {
+    vec3 color = clamp(texture(u_frame, frag_uv).rgb, 0.0, 1.0);
+    finale_color = vec4(mix(color, grade(color), u_intensity), 1.0);
+}
@@ -0,0 +1,6 @@
+#version 330 core
+#include "cardless/post.glsl"
+
+void main() {
+    finale_color = vec4(texture(u_frame, frag_uv).rgb, 1.0);
+}
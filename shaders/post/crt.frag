@@ -0,0 +1,17 @@
+#version 330 core
+#include "cardless/post.glsl"
+
+// Height of a scanline in output pixels, its upper half being dimmed.
+uniform float u_line_height;
+uniform float u_strength;
+
+void main() {
+    vec3 color = texture(u_frame, frag_uv).rgb;
+    float phase = fract(gl_FragCoord.y / u_line_height);
+    float dim = u_strength * step(0.5, phase);
+    // Faint RGB mask along each line.
+    int column = int(gl_FragCoord.x) % 3;
+    vec3 mask = vec3(column == 0, column == 1, column == 2) * 0.15 + 0.85;
+
+    finale_color = vec4(color * (1.0 - dim) * mask, 1.0);
+}
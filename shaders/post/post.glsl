@@ -0,0 +1,8 @@
+in vec2 frag_uv;
+
+// Output of the previous pass, or the captured frame for the first one.
+uniform sampler2D u_frame;
+// Size of the output in pixels.
+uniform vec2 u_resolution;
+
+out vec4 finale_color;
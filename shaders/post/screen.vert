@@ -0,0 +1,9 @@
+#version 330 core
+layout (location = 0) in vec2 vert_pos;
+
+out vec2 frag_uv;
+
+void main() {
+    frag_uv = vert_pos * 0.5 + 0.5;
+    gl_Position = vec4(vert_pos, 0.0, 1.0);
+}
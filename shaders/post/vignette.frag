@@ -0,0 +1,14 @@
+#version 330 core
+#include "cardless/post.glsl"
+
+// Distance from the centre where darkening starts, 1 being the corners.
+uniform float u_radius;
+uniform float u_strength;
+
+void main() {
+    vec3 color = texture(u_frame, frag_uv).rgb;
+    float distance = length(frag_uv - 0.5) / length(vec2(0.5));
+    float shade = smoothstep(u_radius, 1.0, distance) * u_strength;
+
+    finale_color = vec4(color * (1.0 - shade), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 330 core
+in vec2 frag_uv;
+in vec4 frag_color;
+flat in int frag_texture;
+
+out vec4 finale_color;
+
+#include "cardless/textures.glsl"
+
+void main() {
+    vec4 v_color = sample_texture(frag_texture, frag_uv);
+
+    finale_color = v_color * frag_color;
+    // finale_color = vec4(vec3(frag_uv, .0) / (frag_texture + 1), 1);
+    // finale_color = vec4(vec3((frag_texture + 1) / 3.0), 1.0);
+}
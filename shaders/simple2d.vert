@@ -0,0 +1,19 @@
+#version 330 core
+layout (location = 0) in vec2 vert_pos;
+layout (location = 1) in vec2 vert_uv;
+layout (location = 2) in int vert_texture;
+layout (location = 3) in vec4 vert_color;
+layout (location = 4) in float vert_z;
+
+out vec2 frag_uv;
+out vec4 frag_color;
+flat out int frag_texture;
+
+uniform mat4 u_view_projection;
+
+void main() {
+    frag_uv = vert_uv;
+    frag_color = vert_color;
+    frag_texture = vert_texture;
+    gl_Position = u_view_projection * vec4(vert_pos.xy, vert_z, 1.0);
+}
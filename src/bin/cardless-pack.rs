@@ -0,0 +1,126 @@
+//! Packs a directory of assets into a bundle read by `Bundle::try_load`:
+//!
+//! ```text
+//! cardless-pack [--page-size N] <assets directory> <bundle file>
+//! ```
+//!
+//! PNG images go into atlas pages, named by their path relative to the
+//! directory without the extension, e.g. `sprites/player`. `.vert` and `.frag`
+//! shaders are preprocessed, includes resolved, and compiled when built with
+//! the `headless` feature so broken ones are caught before the game starts.
+
+use std::{collections::HashMap, fs::{self, File}, io::{self, BufWriter}, path::{Path, PathBuf}};
+
+use cardless_game_engine::cardless::{atlas::AtlasBuilder, bundle::{PackedBundle, BUNDLE_TEXTURE_SLOTS}, error::CardlessError, post_process::POST_GLSL, shader::{Preprocessed, Preprocessor, ShaderType}};
+
+const USAGE: &str = "usage: cardless-pack [--page-size N] <assets directory> <bundle file>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (page_size, input, output) = match parse_args(&args) {
+        Some(parsed) => parsed,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = pack(page_size, Path::new(input), Path::new(output)) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Option<(u32, &str, &str)> {
+    match args {
+        [flag, size, input, output] if flag == "--page-size" => Some((size.parse().ok()?, input, output)),
+        [input, output] => Some((2048, input, output)),
+        _ => None,
+    }
+}
+
+fn pack(page_size: u32, input: &Path, output: &Path) -> Result<(), CardlessError> {
+    let mut files = Vec::new();
+    collect_files(input, &mut files)?;
+
+    let mut atlas = AtlasBuilder::new(page_size);
+    let mut shaders = Vec::new();
+    let preprocessor = Preprocessor::new()
+        .with_texture_slots(BUNDLE_TEXTURE_SLOTS)
+        .with_file("cardless/post.glsl", POST_GLSL.to_string());
+
+    for path in &files {
+        let relative = path.strip_prefix(input).unwrap();
+        let stage = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => {
+                let image = image::open(path).map_err(|e| CardlessError::File {
+                    path: path.clone(),
+                    error: io::Error::new(io::ErrorKind::InvalidData, e),
+                })?;
+                atlas.add(asset_name(&relative.with_extension("")), image.into_rgba8());
+                continue;
+            }
+            Some("vert") => ShaderType::VERTEX,
+            Some("frag") => ShaderType::FRAGMENT,
+            _ => continue,
+        };
+
+        shaders.push((asset_name(relative), stage, preprocessor.preprocess_file(path)?));
+    }
+
+    validate(&shaders)?;
+
+    let bundle = PackedBundle {
+        atlas: atlas.pack()?,
+        shaders: shaders.into_iter().map(|(name, _, source)| (name, source.source)).collect::<HashMap<_, _>>(),
+    };
+    let file = File::create(output).map_err(|error| CardlessError::File { path: output.to_path_buf(), error })?;
+    bundle.write(BufWriter::new(file))?;
+
+    println!(
+        "packed {} images into {} pages and {} shaders",
+        bundle.atlas.regions.len(), bundle.atlas.pages.len(), bundle.shaders.len(),
+    );
+    Ok(())
+}
+
+/// Files under `directory`, in a stable order.
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), CardlessError> {
+    let entries = fs::read_dir(directory).map_err(|error| CardlessError::File { path: directory.to_path_buf(), error })?;
+    let mut paths = entries.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `path` with `/` separators whatever the platform.
+fn asset_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(feature = "headless")]
+fn validate(shaders: &[(String, ShaderType, Preprocessed)]) -> Result<(), CardlessError> {
+    use cardless_game_engine::cardless::{headless::HeadlessContext, shader::Shader};
+
+    let _context = HeadlessContext::try_new(1, 1)?;
+    for (_, stage, source) in shaders {
+        Shader::try_preprocessed(*stage, source)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "headless"))]
+fn validate(_shaders: &[(String, ShaderType, Preprocessed)]) -> Result<(), CardlessError> {
+    eprintln!("warning: built without the `headless` feature, shaders were preprocessed but not compiled");
+    Ok(())
+}
@@ -0,0 +1,331 @@
+//! Packs many small images into a few large textures, so sprites using
+//! different images still end up in the same draw call:
+//!
+//! ```ignore
+//! let mut builder = AtlasBuilder::new(2048);
+//! builder.try_add_file("player", "assets/player.png")?;
+//! let atlas = builder.try_build()?;
+//!
+//! let region = atlas.region("player").unwrap();
+//! br.draw_region(&Sprite::new(pos, size).with_source(region.rect), &atlas, region);
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use image::{GenericImage, RgbaImage};
+
+use super::{error::CardlessError, sprite::Rect, texture::{self, Texture}};
+
+/// Skyline packer: keeps the lowest free row of every column span of a page
+/// and puts each rectangle where its bottom edge ends up highest.
+pub struct SkylinePacker {
+    width: u32,
+    height: u32,
+    /// Spans covering the page width left to right, `y` being where free
+    /// space starts, counted from the top.
+    skyline: Vec<Span>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+impl SkylinePacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, skyline: vec![Span { x: 0, y: 0, width }] }
+    }
+
+    /// Finds room for a `width` x `height` rectangle and returns its top-left
+    /// corner, or `None` if the page is too full.
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (index, x, y) = (0..self.skyline.len())
+            .filter_map(|index| self.fit(index, width, height).map(|y| (index, self.skyline[index].x, y)))
+            .min_by_key(|&(_, x, y)| (y + height, x))?;
+
+        self.skyline.insert(index, Span { x, y: y + height, width });
+
+        // Cut the spans now lying under the rectangle.
+        let right = x + width;
+        let mut next = index + 1;
+        while next < self.skyline.len() && self.skyline[next].x < right {
+            let span = &mut self.skyline[next];
+            let span_right = span.x + span.width;
+            if span_right <= right {
+                self.skyline.remove(next);
+            } else {
+                span.width = span_right - right;
+                span.x = right;
+                next += 1;
+            }
+        }
+
+        self.skyline.dedup_by(|right, left| {
+            if left.y == right.y {
+                left.width += right.width;
+                true
+            } else {
+                false
+            }
+        });
+
+        Some((x, y))
+    }
+
+    /// Top of a rectangle placed at the start of span `index`, if it fits.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut covered = 0;
+        for span in &self.skyline[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(span.y);
+            covered += span.width;
+        }
+
+        if y + height > self.height {
+            return None;
+        }
+        Some(y)
+    }
+}
+
+/// Where a packed image ended up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    /// Index of the page texture in [`Atlas::pages`].
+    pub page: usize,
+    /// Area of the page in UV space, ready for [`Sprite::with_source`](super::sprite::Sprite::with_source).
+    pub rect: Rect,
+    /// Size of the image in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Whether every pixel of the image has full alpha, unlike the page
+    /// with the padding around it.
+    pub opaque: bool,
+}
+
+/// Result of [`AtlasBuilder::pack`], before uploading the pages.
+pub struct PackedAtlas {
+    pub pages: Vec<RgbaImage>,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+pub struct Atlas {
+    pub pages: Vec<Texture>,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    /// Texture to draw `region` from.
+    pub fn page(&self, region: &AtlasRegion) -> &Texture {
+        &self.pages[region.page]
+    }
+}
+
+impl PackedAtlas {
+    pub fn try_upload(self) -> Result<Atlas, CardlessError> {
+        let pages = self.pages.into_iter()
+            .map(|page| Texture::try_from_image(&image::DynamicImage::ImageRgba8(page)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Atlas { pages, regions: self.regions })
+    }
+}
+
+/// Collects named images to pack into square pages of `page_size` pixels.
+pub struct AtlasBuilder {
+    page_size: u32,
+    padding: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_size: u32) -> Self {
+        Self { page_size, padding: 1, images: Vec::new() }
+    }
+
+    /// Transparent pixels kept around every image so filtering does not
+    /// bleed neighbours in, 1 by default.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Adds `image` under `name`, replacing an image added with the same name.
+    pub fn add<S: Into<String>>(&mut self, name: S, image: RgbaImage) {
+        let name = name.into();
+        self.images.retain(|(other, _)| *other != name);
+        self.images.push((name, image));
+    }
+
+    /// Adds the image at `path` under `name`, e.g. its path relative to the
+    /// asset directory as `cardless-pack` does. Unlike [`Self::add`], a name
+    /// already taken is an error.
+    pub fn try_add_file<S: Into<String>, P: AsRef<Path>>(&mut self, name: S, path: P) -> Result<(), CardlessError> {
+        let name = name.into();
+        if self.images.iter().any(|(other, _)| *other == name) {
+            return Err(CardlessError::DuplicateRegion(name));
+        }
+        let image = image::open(path)?.into_rgba8();
+
+        self.images.push((name, image));
+        Ok(())
+    }
+
+    /// Packs the images tallest first, opening a new page whenever one does
+    /// not fit in any of the previous ones.
+    pub fn pack(mut self) -> Result<PackedAtlas, CardlessError> {
+        self.images.sort_by(|(a_name, a), (b_name, b)| b.height().cmp(&a.height()).then_with(|| a_name.cmp(b_name)));
+
+        let size = self.page_size;
+        let mut packers: Vec<SkylinePacker> = Vec::new();
+        let mut pages: Vec<RgbaImage> = Vec::new();
+        let mut regions = HashMap::new();
+
+        for (name, image) in self.images {
+            let (width, height) = image.dimensions();
+            let padded = (width + 2 * self.padding, height + 2 * self.padding);
+            if padded.0 > size || padded.1 > size {
+                return Err(CardlessError::AtlasOverflow { name, width, height, page_size: size });
+            }
+
+            let found = packers.iter_mut().enumerate()
+                .find_map(|(page, packer)| packer.pack(padded.0, padded.1).map(|corner| (page, corner)));
+            let (page, (x, y)) = match found {
+                Some(found) => found,
+                None => {
+                    let mut packer = SkylinePacker::new(size, size);
+                    let corner = packer.pack(padded.0, padded.1).unwrap();
+                    packers.push(packer);
+                    pages.push(RgbaImage::new(size, size));
+                    (pages.len() - 1, corner)
+                }
+            };
+
+            let (x, y) = (x + self.padding, y + self.padding);
+            // Fits by construction.
+            pages[page].copy_from(&image, x, y).unwrap();
+
+            // Pages are flipped on upload, so V grows from the bottom row.
+            let rect = Rect::new(
+                glm::vec2(x as f32 / size as f32, 1. - (y + height) as f32 / size as f32),
+                glm::vec2(width as f32 / size as f32, height as f32 / size as f32),
+            );
+            let opaque = texture::opaque_rgba(image.as_raw());
+            regions.insert(name, AtlasRegion { page, rect, width, height, opaque });
+        }
+
+        Ok(PackedAtlas { pages, regions })
+    }
+
+    pub fn try_build(self) -> Result<Atlas, CardlessError> {
+        self.pack()?.try_upload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use crate::cardless::error::CardlessError;
+
+    use super::{AtlasBuilder, SkylinePacker};
+
+    #[test]
+    fn skyline_fills_the_page_without_overlaps() {
+        let mut packer = SkylinePacker::new(64, 64);
+        let sizes = [(32, 32), (16, 32), (16, 16), (16, 16), (64, 16), (32, 16)];
+
+        let mut placed: Vec<(u32, u32, u32, u32)> = Vec::new();
+        for &(width, height) in &sizes {
+            let (x, y) = packer.pack(width, height).unwrap();
+            assert!(x + width <= 64 && y + height <= 64);
+            for &(ox, oy, ow, oh) in &placed {
+                assert!(x >= ox + ow || ox >= x + width || y >= oy + oh || oy >= y + height);
+            }
+            placed.push((x, y, width, height));
+        }
+
+        // Only the 32x16 corner at the bottom right is left.
+        assert_eq!(packer.pack(32, 17), None);
+        assert_eq!(packer.pack(32, 16), Some((32, 48)));
+        assert_eq!(packer.pack(1, 1), None);
+    }
+
+    #[test]
+    fn spills_over_to_new_pages() {
+        let mut builder = AtlasBuilder::new(64).with_padding(0);
+        for i in 0..5 {
+            builder.add(format!("tile{}", i), RgbaImage::new(32, 32));
+        }
+        builder.add("small", RgbaImage::new(8, 8));
+        let atlas = builder.pack().unwrap();
+
+        assert_eq!(atlas.pages.len(), 2);
+        assert_eq!(atlas.regions["tile4"].page, 1);
+        assert_eq!(atlas.regions["small"].page, 1);
+
+        let region = atlas.regions["tile0"];
+        assert_eq!(region.rect.size, glm::vec2(0.5, 0.5));
+        assert_eq!(region.rect.position, glm::vec2(0., 0.5));
+    }
+
+    #[test]
+    fn copies_pixels_inside_the_padding() {
+        let mut builder = AtlasBuilder::new(16).with_padding(2);
+        builder.add("red", RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])));
+        let atlas = builder.pack().unwrap();
+
+        let page = &atlas.pages[0];
+        assert_eq!(page.get_pixel(1, 1).0, [0, 0, 0, 0]);
+        assert_eq!(page.get_pixel(2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(page.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(page.get_pixel(6, 6).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn regions_know_if_their_image_is_opaque() {
+        let mut builder = AtlasBuilder::new(16);
+        builder.add("solid", RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])));
+        builder.add("glass", RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 128])));
+        let atlas = builder.pack().unwrap();
+
+        assert!(atlas.regions["solid"].opaque);
+        assert!(!atlas.regions["glass"].opaque);
+    }
+
+    #[test]
+    fn rejects_images_larger_than_a_page() {
+        let mut builder = AtlasBuilder::new(32);
+        builder.add("huge", RgbaImage::new(31, 8));
+
+        match builder.pack() {
+            Err(CardlessError::AtlasOverflow { name, .. }) => assert_eq!(name, "huge"),
+            _ => panic!("expected an overflow"),
+        }
+    }
+
+    #[test]
+    fn file_names_must_be_unique() {
+        let mut builder = AtlasBuilder::new(64);
+        builder.add("a/player", RgbaImage::new(8, 8));
+
+        match builder.try_add_file("a/player", "b/player.png") {
+            Err(CardlessError::DuplicateRegion(name)) => assert_eq!(name, "a/player"),
+            _ => panic!("expected a duplicate region error"),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::mem::size_of;
 
-use super::{vertex_attribute::Vertex, buffer::{Buffer, BufferType}, texture::Texture};
+use super::{device, vertex_attribute::Vertex, buffer::{Buffer, BufferType, StreamingStrategy}, error::CardlessError, vertex_array::VertexArray};
 
 pub struct Batch<T>
 where T: Vertex {
@@ -12,36 +12,57 @@ where T: Vertex {
 
     pub textures_capacity: usize,
     pub textures: Vec<u32>,
+
+    pub vertex_array: VertexArray,
 }
 
 
 impl<T> Batch<T>
 where T: Vertex {
-    pub fn new() -> Self {
-        let textures_capacity = 16;
-        Self {
+    pub fn try_new() -> Result<Self, CardlessError> {
+        // Every slot needs its own texture unit in the fragment shader.
+        let textures_capacity = device::with(|device| device.max_texture_image_units()).min(16) as usize;
+        let vbo = Buffer::try_new(BufferType::VERTEX, Vec::new(), size_of::<T>())?;
+        let ebo = Buffer::try_new(BufferType::ELEMENT, Vec::new(), size_of::<u32>())?;
+        let vertex_array = VertexArray::try_new(&vbo, &ebo)?;
+
+        Ok(Self {
             vbo_capacity: 2048,
-            vbo: Buffer::new(BufferType::VERTEX, Vec::new(), size_of::<T>()),
+            vbo,
             ebo_capacity: 2048,
-            ebo: Buffer::new(BufferType::ELEMENT, Vec::new(), size_of::<u32>()),
+            ebo,
             textures_capacity,
             textures: Vec::with_capacity(textures_capacity),
-        }
+            vertex_array,
+        })
+    }
+
+    /// Streams both buffers with `strategy`, preallocating room for
+    /// `vbo_capacity` vertices and `ebo_capacity` indices.
+    pub fn set_streaming(&mut self, strategy: StreamingStrategy) -> Result<(), CardlessError> {
+        self.vbo.set_streaming(strategy, self.vbo_capacity)?;
+        self.ebo.set_streaming(strategy, self.ebo_capacity)
     }
 
-    pub fn get_texture_slot(&mut self, texture: &Texture) -> Option<i32> {
-        match self.textures.iter().position(|&id| id == texture.handler) {
-            Some(slot) => Some(slot as i32),
-            None => {
-                let next_slot = self.textures.len();
-                if next_slot < self.textures_capacity {
-                    self.textures.push(texture.handler);
-                    Some(next_slot as i32)
-                } else {
-                    None
-                }
+    /// Slot of the texture with handler `texture`, taking a free one if needed.
+    pub fn get_texture_slot(&mut self, texture: u32) -> Option<i32> {
+        texture_slot(&mut self.textures, self.textures_capacity, texture)
+    }
+}
+
+/// Slot of the texture with handler `texture` among `textures`, taking a
+/// free one if there are fewer than `capacity`.
+pub fn texture_slot(textures: &mut Vec<u32>, capacity: usize, texture: u32) -> Option<i32> {
+    match textures.iter().position(|&id| id == texture) {
+        Some(slot) => Some(slot as i32),
+        None => {
+            let next_slot = textures.len();
+            if next_slot < capacity {
+                textures.push(texture);
+                Some(next_slot as i32)
+            } else {
+                None
             }
         }
     }
 }
-
@@ -1,8 +1,46 @@
-use std::ffi::c_void;
+use std::{cell::Cell, slice};
+
+use super::{device, error::{CardlessError, check_gl}};
 
 pub enum BufferType {
     VERTEX,
     ELEMENT,
+    /// Data shared between programs through uniform blocks, see [`Buffer::bind_base`].
+    /// `T` has to follow the std140 layout, e.g. pad a `vec3` to 16 bytes.
+    UNIFORM,
+}
+
+/// How [`Buffer::flush`] gets the data to the GPU, see [`Buffer::set_streaming`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamingStrategy {
+    /// `glBufferData` with exactly the data, reallocating storage on every flush.
+    Reallocate,
+    /// Orphans the storage with `glBufferData(NULL)` and fills the new one
+    /// with `glBufferSubData`, so the upload never waits for draws still
+    /// reading the old storage.
+    Orphan,
+    /// `glBufferSubData` into storage allocated once. The driver may stall
+    /// until the draws reading the previous data are done.
+    SubData,
+    /// Storage for `regions` batches written in turn through unsynchronized
+    /// `glMapBufferRange`, a fence keeping each region from being overwritten
+    /// while draws still read it. Draws have to start at the offset
+    /// [`Buffer::flush`] returns.
+    Ring { regions: usize },
+}
+
+thread_local! {
+    static UPLOADED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Bytes every buffer of this thread uploaded since the last call; calling
+/// it once per frame gives the upload traffic of each frame.
+pub fn take_uploaded_bytes() -> usize {
+    UPLOADED.with(|uploaded| uploaded.replace(0))
+}
+
+fn count_upload(bytes: usize) {
+    UPLOADED.with(|uploaded| uploaded.set(uploaded.get() + bytes));
 }
 
 pub struct Buffer<T>
@@ -11,40 +49,202 @@ where T: Sized {
     pub data: Vec<T>,
     buffer_type: u32,
     t_size: usize,
+    streaming: StreamingStrategy,
+    /// Elements the storage (or each ring region) holds.
+    capacity: usize,
+    region: Option<usize>,
+    fences: Vec<Option<usize>>,
 }
 
 impl<T> Buffer<T>
 where T: Sized {
-    pub fn new(buffer_type: BufferType, data: Vec<T>, size_of: usize) -> Self {
-        let mut handler = 0;
+    pub fn try_new(buffer_type: BufferType, data: Vec<T>, size_of: usize) -> Result<Self, CardlessError> {
         let buffer_type = match buffer_type {
             BufferType::VERTEX => gl::ARRAY_BUFFER,
             BufferType::ELEMENT => gl::ELEMENT_ARRAY_BUFFER,
+            BufferType::UNIFORM => gl::UNIFORM_BUFFER,
         };
 
-        let bytes_len = data.len() * size_of;
-        let bytes_ptr = data.as_ptr() as *const c_void;
+        let bytes = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of) };
+        count_upload(bytes.len());
+
+        let handler = device::with(|device| {
+            let handler = device.create_buffer();
+            device.bind_buffer(buffer_type, handler);
+            device.buffer_data(buffer_type, bytes, gl::STATIC_DRAW);
+            handler
+        });
+
+        let buffer = Self {
+            handler,
+            data,
+            t_size: size_of,
+            buffer_type,
+            streaming: StreamingStrategy::Reallocate,
+            capacity: 0,
+            region: None,
+            fences: Vec::new(),
+        };
+        check_gl("buffer creation")?;
+
+        Ok(buffer)
+    }
+
+    /// Switches to `strategy` with storage for `capacity` elements, which
+    /// grows if a flush brings more. Buffers start with
+    /// [`StreamingStrategy::Reallocate`].
+    pub fn set_streaming(&mut self, strategy: StreamingStrategy, capacity: usize) -> Result<(), CardlessError> {
+        self.streaming = match strategy {
+            StreamingStrategy::Ring { regions } => StreamingStrategy::Ring { regions: regions.max(1) },
+            strategy => strategy,
+        };
+        self.capacity = capacity;
+
+        device::with(|device| device.bind_buffer(self.buffer_type, self.handler));
+        self.allocate();
+        check_gl("buffer allocation")
+    }
+
+    pub fn streaming(&self) -> StreamingStrategy {
+        self.streaming
+    }
 
-        unsafe { gl::GenBuffers(1, &mut handler); }
-        unsafe { gl::BindBuffer(buffer_type, handler); }
-        unsafe { gl::BufferData(buffer_type, bytes_len as isize, bytes_ptr, gl::STATIC_DRAW); }
+    /// Replaces the storage with one sized for the current strategy. Draws
+    /// issued before keep reading the old storage, so pending fences go.
+    fn allocate(&mut self) {
+        let (size, usage, regions) = match self.streaming {
+            StreamingStrategy::Reallocate => return,
+            StreamingStrategy::Orphan => (self.capacity, gl::STREAM_DRAW, 0),
+            StreamingStrategy::SubData => (self.capacity, gl::DYNAMIC_DRAW, 0),
+            StreamingStrategy::Ring { regions } => (self.capacity * regions, gl::STREAM_DRAW, regions),
+        };
+        let fences: Vec<usize> = self.fences.drain(..).flatten().collect();
+        self.fences.resize(regions, None);
+        self.region = None;
 
-        Self { handler, data, t_size: size_of, buffer_type }
+        device::with(|device| {
+            for fence in fences {
+                device.delete_sync(fence);
+            }
+            device.allocate_buffer(self.buffer_type, size * self.t_size, usage);
+        });
     }
 
-    pub fn flush(&mut self) {
-        let bytes_len = self.data.len() * self.t_size;
-        let bytes_ptr = self.data.as_ptr() as *const c_void;
+    /// Uploads `data` and returns the index of the element it starts at in
+    /// the storage, which is 0 unless streaming through a
+    /// [`StreamingStrategy::Ring`].
+    pub fn flush(&mut self) -> usize {
+        let bytes = unsafe { slice::from_raw_parts(self.data.as_ptr() as *const u8, self.data.len() * self.t_size) };
+        count_upload(bytes.len());
+
+        device::with(|device| device.bind_buffer(self.buffer_type, self.handler));
+        if self.data.len() > self.capacity && self.streaming != StreamingStrategy::Reallocate {
+            self.capacity = self.data.len();
+            self.allocate();
+        }
+
+        device::with(|device| match self.streaming {
+            StreamingStrategy::Reallocate => {
+                device.buffer_data(self.buffer_type, bytes, gl::DYNAMIC_DRAW);
+                0
+            }
+            StreamingStrategy::Orphan => {
+                device.allocate_buffer(self.buffer_type, self.capacity * self.t_size, gl::STREAM_DRAW);
+                device.buffer_sub_data(self.buffer_type, 0, bytes);
+                0
+            }
+            StreamingStrategy::SubData => {
+                device.buffer_sub_data(self.buffer_type, 0, bytes);
+                0
+            }
+            StreamingStrategy::Ring { regions } => {
+                // Whatever draws read the previous region were issued by now.
+                if let Some(previous) = self.region {
+                    self.fences[previous] = Some(device.fence_sync());
+                }
 
+                let region = self.region.map_or(0, |previous| (previous + 1) % regions);
+                if let Some(fence) = self.fences[region].take() {
+                    device.wait_sync(fence);
+                    device.delete_sync(fence);
+                }
+                self.region = Some(region);
 
-        unsafe { gl::BindBuffer(self.buffer_type, self.handler); }
-        unsafe { gl::BufferData(self.buffer_type, bytes_len as isize, bytes_ptr, gl::DYNAMIC_DRAW); }
+                let first = region * self.capacity;
+                if !bytes.is_empty() {
+                    let access = gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT | gl::MAP_UNSYNCHRONIZED_BIT;
+                    device.write_mapped_buffer(self.buffer_type, first * self.t_size, bytes, access);
+                }
+                first
+            }
+        })
+    }
+
+    /// Makes a uniform buffer the source of every block bound to `binding`,
+    /// see [`ShaderProgram::bind_uniform_block`](super::shader_program::ShaderProgram::bind_uniform_block).
+    pub fn bind_base(&self, binding: u32) {
+        device::with(|device| device.bind_buffer_base(self.buffer_type, binding, self.handler));
     }
 }
 
 impl<T> Drop for Buffer<T> {
     fn drop(&mut self) {
-        unsafe { gl::DeleteBuffers(1, &self.handler); }
+        device::with(|device| {
+            for &fence in self.fences.iter().flatten() {
+                device.delete_sync(fence);
+            }
+            device.delete_buffer(self.handler);
+        });
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::cardless::device::{self, Command};
+
+    use super::{take_uploaded_bytes, Buffer, BufferType, StreamingStrategy};
+
+    #[test]
+    fn ring_waits_for_the_region_it_reuses() {
+        let log = device::record();
+
+        let mut buffer = Buffer::try_new(BufferType::VERTEX, Vec::new(), 4).unwrap();
+        buffer.set_streaming(StreamingStrategy::Ring { regions: 2 }, 8).unwrap();
+        buffer.data = vec![1u32, 2];
+        log.borrow_mut().clear();
+
+        assert_eq!(buffer.flush(), 0);
+        assert_eq!(buffer.flush(), 8);
+        assert_eq!(buffer.flush(), 0);
+
+        let log = log.borrow();
+        let syncs: Vec<&Command> = log.iter().filter(|command| matches!(command,
+            Command::FenceSync(_) | Command::WaitSync(_) | Command::DeleteSync(_)
+        )).collect();
+        match syncs[..] {
+            [Command::FenceSync(first), Command::FenceSync(second), Command::WaitSync(waited), Command::DeleteSync(deleted)] => {
+                assert_ne!(first, second);
+                assert_eq!(waited, first);
+                assert_eq!(deleted, first);
+            }
+            _ => panic!("unexpected {:?}", syncs),
+        }
+    }
+
+    #[test]
+    fn counts_uploaded_bytes() {
+        device::record();
+        take_uploaded_bytes();
+
+        let mut buffer = Buffer::try_new(BufferType::VERTEX, vec![0u32; 3], 4).unwrap();
+        buffer.set_streaming(StreamingStrategy::SubData, 16).unwrap();
+        buffer.flush();
+        assert_eq!(take_uploaded_bytes(), 24);
+
+        // Storage grows when the data outgrows it.
+        buffer.data = vec![0; 20];
+        buffer.flush();
+        assert_eq!(take_uploaded_bytes(), 80);
+        assert_eq!(take_uploaded_bytes(), 0);
+    }
+}
@@ -0,0 +1,227 @@
+//! Asset bundles written by the `cardless-pack` tool: atlas pages stored as
+//! raw RGBA so loading needs no image decoding, their regions, and shaders
+//! already preprocessed with [`BUNDLE_TEXTURE_SLOTS`] texture slots.
+//!
+//! All integers are little-endian `u32`, floats `f32` and strings a byte
+//! length followed by UTF-8:
+//!
+//! ```text
+//! "CLBUNDLE" version
+//! page count, then per page: width height pixels
+//! region count, then per region: name page x y width height pixel_width pixel_height opaque
+//! shader count, then per shader: name source
+//! ```
+
+use std::{collections::HashMap, io::{Read, Write}};
+
+use image::RgbaImage;
+
+use super::{atlas::{Atlas, AtlasRegion, PackedAtlas}, error::CardlessError, sprite::Rect};
+
+const MAGIC: &[u8; 8] = b"CLBUNDLE";
+const VERSION: u32 = 2;
+
+/// Texture slots bundled shaders are preprocessed for. It is the number of
+/// fragment texture units every OpenGL 3.3 driver has, so batches always get
+/// exactly this many.
+pub const BUNDLE_TEXTURE_SLOTS: usize = 16;
+
+/// Bundle contents before the pages are uploaded.
+pub struct PackedBundle {
+    pub atlas: PackedAtlas,
+    /// Preprocessed sources by path relative to the packed directory,
+    /// e.g. `shaders/simple2d.vert`.
+    pub shaders: HashMap<String, String>,
+}
+
+impl PackedBundle {
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), CardlessError> {
+        out.write_all(MAGIC)?;
+        write_u32(&mut out, VERSION)?;
+
+        write_u32(&mut out, self.atlas.pages.len() as u32)?;
+        for page in &self.atlas.pages {
+            write_u32(&mut out, page.width())?;
+            write_u32(&mut out, page.height())?;
+            out.write_all(page.as_raw())?;
+        }
+
+        // Sorted so that packing the same assets gives the same bytes.
+        let mut regions: Vec<_> = self.atlas.regions.iter().collect();
+        regions.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(&mut out, regions.len() as u32)?;
+        for (name, region) in regions {
+            write_str(&mut out, name)?;
+            write_u32(&mut out, region.page as u32)?;
+            for value in &[region.rect.position.x, region.rect.position.y, region.rect.size.x, region.rect.size.y] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+            write_u32(&mut out, region.width)?;
+            write_u32(&mut out, region.height)?;
+            write_u32(&mut out, region.opaque as u32)?;
+        }
+
+        let mut shaders: Vec<_> = self.shaders.iter().collect();
+        shaders.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(&mut out, shaders.len() as u32)?;
+        for (name, source) in shaders {
+            write_str(&mut out, name)?;
+            write_str(&mut out, source)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut data: R) -> Result<Self, CardlessError> {
+        let mut bytes = Vec::new();
+        data.read_to_end(&mut bytes)?;
+        let mut reader = Reader { bytes: &bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a cardless asset bundle"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let mut pages = Vec::new();
+        for _ in 0..reader.u32()? {
+            let width = reader.u32()?;
+            let height = reader.u32()?;
+            let pixels = reader.take(width as usize * height as usize * 4)?.to_vec();
+            pages.push(RgbaImage::from_raw(width, height, pixels).unwrap());
+        }
+
+        let mut regions = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let page = reader.u32()? as usize;
+            if page >= pages.len() {
+                return Err(invalid(&format!("region `{}` is on missing page {}", name, page)));
+            }
+            let rect = Rect::new(glm::vec2(reader.f32()?, reader.f32()?), glm::vec2(reader.f32()?, reader.f32()?));
+            let width = reader.u32()?;
+            let height = reader.u32()?;
+            let opaque = reader.u32()? != 0;
+            regions.insert(name, AtlasRegion { page, rect, width, height, opaque });
+        }
+
+        let mut shaders = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            shaders.insert(name, reader.string()?);
+        }
+
+        Ok(Self { atlas: PackedAtlas { pages, regions }, shaders })
+    }
+
+    pub fn try_upload(self) -> Result<Bundle, CardlessError> {
+        Ok(Bundle { atlas: self.atlas.try_upload()?, shaders: self.shaders })
+    }
+}
+
+pub struct Bundle {
+    pub atlas: Atlas,
+    pub shaders: HashMap<String, String>,
+}
+
+impl Bundle {
+    /// Reads a bundle written by `cardless-pack` and uploads its pages.
+    pub fn try_load<R: Read>(data: R) -> Result<Self, CardlessError> {
+        PackedBundle::read(data)?.try_upload()
+    }
+
+    /// Preprocessed source of the shader packed from `name`.
+    pub fn shader(&self, name: &str) -> Option<&str> {
+        self.shaders.get(name).map(String::as_str)
+    }
+}
+
+fn invalid(message: &str) -> CardlessError {
+    CardlessError::Bundle(message.to_string())
+}
+
+fn write_u32<W: Write>(out: &mut W, value: u32) -> Result<(), CardlessError> {
+    out.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_str<W: Write>(out: &mut W, value: &str) -> Result<(), CardlessError> {
+    write_u32(out, value.len() as u32)?;
+    out.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], CardlessError> {
+        if count > self.bytes.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, CardlessError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn f32(&mut self) -> Result<f32, CardlessError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn string(&mut self) -> Result<String, CardlessError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use image::RgbaImage;
+
+    use crate::cardless::{atlas::AtlasBuilder, error::CardlessError};
+
+    use super::PackedBundle;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let mut builder = AtlasBuilder::new(32);
+        builder.add("red", RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255])));
+        builder.add("blue", RgbaImage::from_pixel(3, 3, image::Rgba([0, 0, 255, 255])));
+        let mut shaders = HashMap::new();
+        shaders.insert("shaders/sprite.frag".to_string(), "#version 330 core\nvoid main() {}\n".to_string());
+        let bundle = PackedBundle { atlas: builder.pack().unwrap(), shaders };
+
+        let mut bytes = Vec::new();
+        bundle.write(&mut bytes).unwrap();
+        let read = PackedBundle::read(&bytes[..]).unwrap();
+
+        assert_eq!(read.atlas.pages, bundle.atlas.pages);
+        assert_eq!(read.atlas.regions, bundle.atlas.regions);
+        assert_eq!(read.shaders, bundle.shaders);
+    }
+
+    #[test]
+    fn rejects_truncated_bundles() {
+        let bundle = PackedBundle { atlas: AtlasBuilder::new(8).pack().unwrap(), shaders: HashMap::new() };
+        let mut bytes = Vec::new();
+        bundle.write(&mut bytes).unwrap();
+
+        match PackedBundle::read(&bytes[..bytes.len() - 1]) {
+            Err(CardlessError::Bundle(message)) => assert_eq!(message, "unexpected end of data"),
+            _ => panic!("expected a bundle error"),
+        }
+        assert!(matches!(PackedBundle::read(&b"PNG"[..]), Err(CardlessError::Bundle(_))));
+    }
+}
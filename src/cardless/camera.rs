@@ -0,0 +1,177 @@
+//! Maps world positions onto the screen. At zoom 1 a world unit is one
+//! pixel, whatever the size of the viewport, so nothing gets stretched:
+//!
+//! ```ignore
+//! let mut camera = Camera2D::new(vec2(800., 600.)).with_position(player.position);
+//! br.set_camera(&camera);
+//!
+//! let (x, y) = window.get_cursor_pos();
+//! let picked = camera.screen_to_world(vec2(x as f32, y as f32));
+//! ```
+
+use super::{device, sprite::Rect};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    /// World position shown at the centre of the viewport.
+    pub position: glm::Vec2,
+    /// Pixels per world unit.
+    pub zoom: f32,
+    /// Counter-clockwise rotation of the camera in radians, which turns the
+    /// world clockwise on screen.
+    pub rotation: f32,
+    /// Area of the window drawn to, in pixels from its top-left corner with
+    /// `y` growing downwards, as cursor positions are.
+    pub viewport: Rect,
+}
+
+impl Camera2D {
+    /// Camera centred on the origin, covering a whole window of `size` pixels.
+    pub fn new(size: glm::Vec2) -> Self {
+        Self {
+            position: glm::vec2(0., 0.),
+            zoom: 1.,
+            rotation: 0.,
+            viewport: Rect::new(glm::vec2(0., 0.), size),
+        }
+    }
+
+    pub fn with_position(mut self, position: glm::Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Rect) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Pixels to normalised device coordinates, per axis.
+    fn scale(&self) -> glm::Vec2 {
+        glm::vec2(2. * self.zoom / self.viewport.size.x, 2. * self.zoom / self.viewport.size.y)
+    }
+
+    /// Orthographic projection of the view, for the `u_view_projection`
+    /// uniform of the renderers.
+    pub fn view_projection(&self) -> glm::Mat4 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let scale = self.scale();
+        let position = self.position;
+
+        glm::Mat4::new(
+            glm::vec4(scale.x * cos, -scale.y * sin, 0., 0.),
+            glm::vec4(scale.x * sin, scale.y * cos, 0., 0.),
+            glm::vec4(0., 0., 1., 0.),
+            glm::vec4(
+                -scale.x * (cos * position.x + sin * position.y),
+                -scale.y * (cos * position.y - sin * position.x),
+                0.,
+                1.,
+            ),
+        )
+    }
+
+    /// Window position in pixels of the world point `world`.
+    pub fn world_to_screen(&self, world: glm::Vec2) -> glm::Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let offset = world - self.position;
+        let view = glm::vec2(cos * offset.x + sin * offset.y, cos * offset.y - sin * offset.x) * self.zoom;
+
+        let viewport = self.viewport;
+        glm::vec2(
+            viewport.position.x + viewport.size.x / 2. + view.x,
+            viewport.position.y + viewport.size.y / 2. - view.y,
+        )
+    }
+
+    /// World point under the window position `screen`, e.g. the cursor.
+    pub fn screen_to_world(&self, screen: glm::Vec2) -> glm::Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let viewport = self.viewport;
+        let view = glm::vec2(
+            screen.x - viewport.position.x - viewport.size.x / 2.,
+            viewport.position.y + viewport.size.y / 2. - screen.y,
+        ) / self.zoom;
+
+        self.position + glm::vec2(cos * view.x - sin * view.y, sin * view.x + cos * view.y)
+    }
+
+    /// Sets the OpenGL viewport to the camera's, `target_height` being the
+    /// height in pixels of the framebuffer drawn to.
+    pub fn apply_viewport(&self, target_height: u32) {
+        let viewport = self.viewport;
+        let bottom = target_height as f32 - viewport.position.y - viewport.size.y;
+        device::with(|device| {
+            device.viewport(
+                viewport.position.x.round() as i32,
+                bottom.round() as i32,
+                viewport.size.x.round() as i32,
+                viewport.size.y.round() as i32,
+            )
+        });
+    }
+}
+
+/// Projection leaving positions as they are, normalised device coordinates.
+pub fn identity() -> glm::Mat4 {
+    glm::Mat4::new(
+        glm::vec4(1., 0., 0., 0.),
+        glm::vec4(0., 1., 0., 0.),
+        glm::vec4(0., 0., 1., 0.),
+        glm::vec4(0., 0., 0., 1.),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::vec2;
+
+    use crate::cardless::sprite::Rect;
+
+    use super::Camera2D;
+
+    fn close(a: glm::Vec2, b: glm::Vec2) -> bool {
+        (a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3
+    }
+
+    #[test]
+    fn projection_keeps_world_units_square() {
+        let camera = Camera2D::new(vec2(800., 600.)).with_zoom(100.);
+        let projection = camera.view_projection();
+
+        let corner = projection * glm::vec4(4., 3., 0., 1.);
+        assert!(close(vec2(corner.x, corner.y), vec2(1., 1.)));
+        let unit = projection * glm::vec4(1., 1., 0., 1.);
+        assert!(close(vec2(unit.x * 400., unit.y * 300.), vec2(100., 100.)));
+    }
+
+    #[test]
+    fn screen_and_world_round_trip() {
+        let camera = Camera2D::new(vec2(800., 600.))
+            .with_position(vec2(10., -5.))
+            .with_zoom(2.)
+            .with_rotation(0.7)
+            .with_viewport(Rect::new(vec2(100., 50.), vec2(400., 300.)));
+
+        assert!(close(camera.world_to_screen(vec2(10., -5.)), vec2(300., 200.)));
+        for &point in &[vec2(0., 0.), vec2(37., 12.), vec2(-20., 90.)] {
+            assert!(close(camera.screen_to_world(camera.world_to_screen(point)), point));
+        }
+
+        // Screen positions agree with the projection used for drawing.
+        let world = vec2(25., 3.);
+        let clip = camera.view_projection() * glm::vec4(world.x, world.y, 0., 1.);
+        let screen = camera.world_to_screen(world);
+        assert!(close(vec2(clip.x, clip.y), vec2((screen.x - 100.) / 200. - 1., 1. - (screen.y - 50.) / 150.)));
+    }
+}
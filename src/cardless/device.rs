@@ -0,0 +1,1195 @@
+use std::{cell::RefCell, ffi::{CStr, CString, c_void}, os::raw::c_char, ptr::null, rc::Rc};
+
+use super::{uniform::ActiveUniform, vertex_attribute::{ActiveAttribute, VertexAttribute}};
+
+/// `GL_TEXTURE_MAX_ANISOTROPY`, core since OpenGL 4.6 and missing from the `gl` crate.
+pub const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+/// `GL_MAX_TEXTURE_MAX_ANISOTROPY`.
+pub const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+/// Everything the renderer asks of the graphics API. Enum arguments are the
+/// plain OpenGL values (`gl::ARRAY_BUFFER`, `gl::TEXTURE_2D`, ...), so devices
+/// other than [`GlDevice`] only have to interpret the ones they care about.
+pub trait RenderDevice {
+    fn create_buffer(&mut self) -> u32;
+    fn bind_buffer(&mut self, target: u32, buffer: u32);
+    fn buffer_data(&mut self, target: u32, data: &[u8], usage: u32);
+    /// Gives the buffer bound to `target` `size` bytes of new, uninitialised
+    /// storage. The old storage lives on until pending draws are done with it.
+    fn allocate_buffer(&mut self, target: u32, size: usize, usage: u32);
+    fn buffer_sub_data(&mut self, target: u32, offset: usize, data: &[u8]);
+    /// Maps `data.len()` bytes at `offset` with the `access` bits
+    /// (`gl::MAP_WRITE_BIT | ...`), copies `data` there and unmaps them.
+    fn write_mapped_buffer(&mut self, target: u32, offset: usize, data: &[u8], access: u32);
+    fn delete_buffer(&mut self, buffer: u32);
+    /// Binds `buffer` to slot `index` of an indexed target such as `gl::UNIFORM_BUFFER`.
+    fn bind_buffer_base(&mut self, target: u32, index: u32, buffer: u32);
+
+    fn create_texture(&mut self) -> u32;
+    fn active_texture(&mut self, slot: u32);
+    fn bind_texture(&mut self, texture: u32);
+    fn texture_parameter(&mut self, parameter: u32, value: i32);
+    fn texture_parameter_f32(&mut self, parameter: u32, values: &[f32]);
+    /// Empty `pixels` allocate the storage without initialising it.
+    fn texture_image_2d(&mut self, internal_format: u32, width: u32, height: u32, format: u32, pixels: &[u8]);
+    fn texture_sub_image_2d(&mut self, x: u32, y: u32, width: u32, height: u32, format: u32, pixels: &[u8]);
+    fn generate_mipmap(&mut self);
+    fn delete_texture(&mut self, texture: u32);
+    /// `GL_MAX_TEXTURE_IMAGE_UNITS`, the number of textures a fragment shader can sample.
+    fn max_texture_image_units(&mut self) -> u32;
+    /// Highest anisotropic filtering level, 1 if the driver has no
+    /// `texture_filter_anisotropic` extension.
+    fn max_anisotropy(&mut self) -> f32;
+
+    fn create_shader(&mut self, shader_type: u32) -> u32;
+    fn compile_shader(&mut self, shader: u32, source: &str) -> bool;
+    fn shader_info_log(&mut self, shader: u32) -> String;
+    fn delete_shader(&mut self, shader: u32);
+
+    fn create_program(&mut self) -> u32;
+    fn attach_shader(&mut self, program: u32, shader: u32);
+    fn link_program(&mut self, program: u32) -> bool;
+    fn program_info_log(&mut self, program: u32) -> String;
+    fn use_program(&mut self, program: u32);
+    fn delete_program(&mut self, program: u32);
+
+    fn uniform_location(&mut self, program: u32, name: &str) -> Option<i32>;
+    /// Active uniforms of a linked program, with array names stripped of `[0]`.
+    fn active_uniforms(&mut self, program: u32) -> Vec<ActiveUniform>;
+    /// Uploads `v` as consecutive values of `components` (1 to 4) floats each.
+    fn uniform_f32(&mut self, location: i32, components: usize, v: &[f32]);
+    fn uniform_i32(&mut self, location: i32, components: usize, v: &[i32]);
+    fn uniform_u32(&mut self, location: i32, components: usize, v: &[u32]);
+    /// Uploads `v` as consecutive column-major square matrices of `dimension` (2 to 4).
+    fn uniform_matrix(&mut self, location: i32, dimension: usize, v: &[f32]);
+    fn uniform_block_index(&mut self, program: u32, name: &str) -> Option<u32>;
+    fn uniform_block_binding(&mut self, program: u32, block: u32, binding: u32);
+
+    fn create_vertex_array(&mut self) -> u32;
+    /// Binds `vertex_array`, 0 unbinding the current one.
+    fn bind_vertex_array(&mut self, vertex_array: u32);
+    fn delete_vertex_array(&mut self, vertex_array: u32);
+    /// Vertex inputs of a linked program, built-ins such as `gl_VertexID` included.
+    fn active_attributes(&mut self, program: u32) -> Vec<ActiveAttribute>;
+    fn vertex_attribute_pointer(&mut self, index: u32, attribute: &VertexAttribute);
+    fn enable_vertex_attribute(&mut self, index: u32);
+    /// Advances attribute `index` once every `divisor` instances instead of every vertex.
+    fn vertex_attribute_divisor(&mut self, index: u32, divisor: u32);
+
+    /// Draws `count` indices starting from index `first`, each offset by `base_vertex`.
+    fn draw_elements(&mut self, count: usize, first: usize, base_vertex: usize);
+    fn draw_elements_instanced(&mut self, count: usize, instances: usize);
+
+    fn create_framebuffer(&mut self) -> u32;
+    /// Binds `framebuffer` for both drawing and reading, 0 being the default one.
+    fn bind_framebuffer(&mut self, framebuffer: u32);
+    /// Framebuffer draws currently go to.
+    fn bound_framebuffer(&mut self) -> u32;
+    /// Attaches level 0 of `texture` to the bound framebuffer.
+    fn framebuffer_texture(&mut self, attachment: u32, texture: u32);
+    fn framebuffer_renderbuffer(&mut self, attachment: u32, renderbuffer: u32);
+    fn check_framebuffer_status(&mut self) -> u32;
+    fn delete_framebuffer(&mut self, framebuffer: u32);
+    /// Creates a renderbuffer with storage for `width` x `height` pixels.
+    fn create_renderbuffer(&mut self, internal_format: u32, width: u32, height: u32) -> u32;
+    fn delete_renderbuffer(&mut self, renderbuffer: u32);
+    fn viewport(&mut self, x: i32, y: i32, width: i32, height: i32);
+    fn get_viewport(&mut self) -> [i32; 4];
+    /// Clears colour, depth and stencil of the bound framebuffer.
+    fn clear(&mut self, color: [f32; 4]);
+    /// Clears only the colour of the bound framebuffer, to `color`.
+    fn clear_color(&mut self, color: [f32; 4]);
+    /// Clears only the depth of the bound framebuffer, to the far plane.
+    fn clear_depth(&mut self);
+    /// How many times [`Self::clear`] and [`Self::clear_depth`] were called,
+    /// letting renderers tell when a new frame starts.
+    fn depth_clears(&mut self) -> u64;
+    /// Whether fragments are tested against the depth buffer, with `GL_LESS`.
+    fn depth_test(&mut self, enabled: bool);
+    fn get_depth_test(&mut self) -> bool;
+    /// Whether drawn fragments write their depth.
+    fn depth_write(&mut self, enabled: bool);
+    fn get_depth_write(&mut self) -> bool;
+    /// Whether drawn colours are blended by their alpha, as
+    /// `GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA`.
+    fn blend(&mut self, enabled: bool);
+    /// Whether back faces are culled.
+    fn cull(&mut self, enabled: bool);
+    /// Whether the framebuffer draws go to has a depth buffer.
+    fn has_depth(&mut self) -> bool;
+    /// Reads RGBA pixels of the bound framebuffer, bottom row first.
+    fn read_pixels(&mut self, x: i32, y: i32, width: u32, height: u32, pixels: &mut [u8]);
+    /// Copies the `[x0, y0, x1, y1]` area of `source` onto the one of
+    /// `destination` with `filter`, leaving `destination` bound.
+    fn blit_framebuffer(&mut self, source: u32, source_rect: [i32; 4], destination: u32, destination_rect: [i32; 4], filter: u32);
+
+    /// Inserts a fence signalled once the commands issued so far completed.
+    fn fence_sync(&mut self) -> usize;
+    /// Blocks until `fence` is signalled.
+    fn wait_sync(&mut self, fence: usize);
+    fn delete_sync(&mut self, fence: usize);
+    /// Blocks until every command issued so far completed.
+    fn finish(&mut self);
+
+    fn get_error(&mut self) -> u32;
+}
+
+thread_local! {
+    static CURRENT: RefCell<Box<dyn RenderDevice>> = RefCell::new(Box::new(GlDevice::default()));
+}
+
+/// Runs `f` with the device of the current thread, which is [`GlDevice`]
+/// unless replaced with [`set`].
+pub fn with<R, F>(f: F) -> R
+where F: FnOnce(&mut dyn RenderDevice) -> R {
+    CURRENT.with(|device| f(device.borrow_mut().as_mut()))
+}
+
+/// Replaces the device of the current thread, returning the previous one.
+pub fn set(device: Box<dyn RenderDevice>) -> Box<dyn RenderDevice> {
+    CURRENT.with(|current| std::mem::replace(&mut *current.borrow_mut(), device))
+}
+
+/// Makes a fresh [`RecordingDevice`] the device of the current thread and
+/// returns its log.
+#[cfg(test)]
+pub fn record() -> Rc<RefCell<Vec<Command>>> {
+    let recording = RecordingDevice::new();
+    let log = recording.log();
+    set(Box::new(recording));
+    log
+}
+
+/// Forwards everything to the OpenGL context current on this thread.
+#[derive(Default)]
+pub struct GlDevice {
+    depth_clears: u64,
+}
+
+impl RenderDevice for GlDevice {
+    fn create_buffer(&mut self) -> u32 {
+        let mut handler = 0;
+        unsafe { gl::GenBuffers(1, &mut handler); }
+        handler
+    }
+
+    fn bind_buffer(&mut self, target: u32, buffer: u32) {
+        unsafe { gl::BindBuffer(target, buffer); }
+    }
+
+    fn buffer_data(&mut self, target: u32, data: &[u8], usage: u32) {
+        unsafe { gl::BufferData(target, data.len() as isize, data.as_ptr() as *const c_void, usage); }
+    }
+
+    fn allocate_buffer(&mut self, target: u32, size: usize, usage: u32) {
+        unsafe { gl::BufferData(target, size as isize, null(), usage); }
+    }
+
+    fn buffer_sub_data(&mut self, target: u32, offset: usize, data: &[u8]) {
+        unsafe { gl::BufferSubData(target, offset as isize, data.len() as isize, data.as_ptr() as *const c_void); }
+    }
+
+    fn write_mapped_buffer(&mut self, target: u32, offset: usize, data: &[u8], access: u32) {
+        let mapped = unsafe { gl::MapBufferRange(target, offset as isize, data.len() as isize, access) };
+
+        // Mapping can fail, e.g. on a zero length; the data still has to get there.
+        if mapped.is_null() {
+            self.buffer_sub_data(target, offset, data);
+            return;
+        }
+
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), mapped as *mut u8, data.len()); }
+        unsafe { gl::UnmapBuffer(target); }
+    }
+
+    fn delete_buffer(&mut self, buffer: u32) {
+        unsafe { gl::DeleteBuffers(1, &buffer); }
+    }
+
+    fn bind_buffer_base(&mut self, target: u32, index: u32, buffer: u32) {
+        unsafe { gl::BindBufferBase(target, index, buffer); }
+    }
+
+    fn create_texture(&mut self) -> u32 {
+        let mut handler = 0;
+        unsafe { gl::GenTextures(1, &mut handler); }
+        handler
+    }
+
+    fn active_texture(&mut self, slot: u32) {
+        unsafe { gl::ActiveTexture(gl::TEXTURE0 + slot); }
+    }
+
+    fn bind_texture(&mut self, texture: u32) {
+        unsafe { gl::BindTexture(gl::TEXTURE_2D, texture); }
+    }
+
+    fn texture_parameter(&mut self, parameter: u32, value: i32) {
+        unsafe { gl::TexParameteri(gl::TEXTURE_2D, parameter, value); }
+    }
+
+    fn texture_parameter_f32(&mut self, parameter: u32, values: &[f32]) {
+        unsafe { gl::TexParameterfv(gl::TEXTURE_2D, parameter, values.as_ptr()); }
+    }
+
+    fn texture_image_2d(&mut self, internal_format: u32, width: u32, height: u32, format: u32, pixels: &[u8]) {
+        // Rows are tightly packed, whatever their length.
+        // No pixels leave the storage uninitialised.
+        let pixels = if pixels.is_empty() { null() } else { pixels.as_ptr() as *const c_void };
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1); }
+        unsafe { gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, width as i32, height as i32, 0, format, gl::UNSIGNED_BYTE, pixels); }
+    }
+
+    fn texture_sub_image_2d(&mut self, x: u32, y: u32, width: u32, height: u32, format: u32, pixels: &[u8]) {
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1); }
+        unsafe { gl::TexSubImage2D(gl::TEXTURE_2D, 0, x as i32, y as i32, width as i32, height as i32, format, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void); }
+    }
+
+    fn generate_mipmap(&mut self) {
+        unsafe { gl::GenerateMipmap(gl::TEXTURE_2D); }
+    }
+
+    fn delete_texture(&mut self, texture: u32) {
+        unsafe { gl::DeleteTextures(1, &texture); }
+    }
+
+    fn max_texture_image_units(&mut self) -> u32 {
+        let mut units = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut units); }
+        units as u32
+    }
+
+    fn max_anisotropy(&mut self) -> f32 {
+        let mut count = 0;
+        unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count); }
+
+        let supported = (0..count as u32).any(|i| {
+            let name = unsafe { CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i) as *const c_char) };
+            matches!(name.to_bytes(), b"GL_EXT_texture_filter_anisotropic" | b"GL_ARB_texture_filter_anisotropic")
+        });
+        if !supported {
+            return 1.;
+        }
+
+        let mut max = 1.;
+        unsafe { gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max); }
+        max
+    }
+
+    fn create_shader(&mut self, shader_type: u32) -> u32 {
+        unsafe { gl::CreateShader(shader_type) }
+    }
+
+    fn compile_shader(&mut self, shader: u32, source: &str) -> bool {
+        let source = match CString::new(source.as_bytes()) {
+            Ok(source) => source,
+            Err(_) => return false,
+        };
+        unsafe { gl::ShaderSource(shader, 1, &source.as_ptr(), null()); }
+        unsafe { gl::CompileShader(shader); }
+
+        let mut success = gl::TRUE as i32;
+        unsafe { gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success); }
+        success == gl::TRUE as i32
+    }
+
+    fn shader_info_log(&mut self, shader: u32) -> String {
+        let mut length = 0;
+        unsafe { gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length); }
+        if length <= 0 {
+            return String::new();
+        }
+
+        let mut log: Vec<u8> = vec![0; length as usize];
+        let mut written = 0;
+        unsafe { gl::GetShaderInfoLog(shader, length, &mut written, log.as_mut_ptr() as *mut i8); }
+        log.truncate(written.max(0) as usize);
+
+        String::from_utf8_lossy(&log).into_owned()
+    }
+
+    fn delete_shader(&mut self, shader: u32) {
+        unsafe { gl::DeleteShader(shader); }
+    }
+
+    fn create_program(&mut self) -> u32 {
+        unsafe { gl::CreateProgram() }
+    }
+
+    fn attach_shader(&mut self, program: u32, shader: u32) {
+        unsafe { gl::AttachShader(program, shader); }
+    }
+
+    fn link_program(&mut self, program: u32) -> bool {
+        unsafe { gl::LinkProgram(program); }
+
+        let mut success = gl::TRUE as i32;
+        unsafe { gl::GetProgramiv(program, gl::LINK_STATUS, &mut success); }
+        success == gl::TRUE as i32
+    }
+
+    fn program_info_log(&mut self, program: u32) -> String {
+        let mut length = 0;
+        unsafe { gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length); }
+        if length <= 0 {
+            return String::new();
+        }
+
+        let mut log: Vec<u8> = vec![0; length as usize];
+        let mut written = 0;
+        unsafe { gl::GetProgramInfoLog(program, length, &mut written, log.as_mut_ptr() as *mut i8); }
+        log.truncate(written.max(0) as usize);
+
+        String::from_utf8_lossy(&log).into_owned()
+    }
+
+    fn use_program(&mut self, program: u32) {
+        unsafe { gl::UseProgram(program); }
+    }
+
+    fn delete_program(&mut self, program: u32) {
+        unsafe { gl::DeleteProgram(program); }
+    }
+
+    fn uniform_location(&mut self, program: u32, name: &str) -> Option<i32> {
+        let c_name = CString::new(name).ok()?;
+        match unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) } {
+            -1 => None,
+            uniform => Some(uniform),
+        }
+    }
+
+    fn active_uniforms(&mut self, program: u32) -> Vec<ActiveUniform> {
+        let mut count = 0;
+        let mut max_length = 0;
+        unsafe { gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count); }
+        unsafe { gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length); }
+
+        (0..count as u32).map(|index| {
+            let mut name = vec![0u8; max_length.max(1) as usize];
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            unsafe { gl::GetActiveUniform(program, index, name.len() as i32, &mut length, &mut size, &mut gl_type, name.as_mut_ptr() as *mut i8); }
+            name.truncate(length as usize);
+
+            let mut name = String::from_utf8_lossy(&name).into_owned();
+            if name.ends_with("[0]") {
+                name.truncate(name.len() - 3);
+            }
+            let location = self.uniform_location(program, &name);
+
+            ActiveUniform { name, gl_type, size: size as usize, location }
+        }).collect()
+    }
+
+    fn uniform_f32(&mut self, location: i32, components: usize, v: &[f32]) {
+        let count = (v.len() / components) as i32;
+        match components {
+            1 => unsafe { gl::Uniform1fv(location, count, v.as_ptr()); }
+            2 => unsafe { gl::Uniform2fv(location, count, v.as_ptr()); }
+            3 => unsafe { gl::Uniform3fv(location, count, v.as_ptr()); }
+            4 => unsafe { gl::Uniform4fv(location, count, v.as_ptr()); }
+            _ => panic!("uniforms have 1 to 4 components, not {}", components),
+        }
+    }
+
+    fn uniform_i32(&mut self, location: i32, components: usize, v: &[i32]) {
+        let count = (v.len() / components) as i32;
+        match components {
+            1 => unsafe { gl::Uniform1iv(location, count, v.as_ptr()); }
+            2 => unsafe { gl::Uniform2iv(location, count, v.as_ptr()); }
+            3 => unsafe { gl::Uniform3iv(location, count, v.as_ptr()); }
+            4 => unsafe { gl::Uniform4iv(location, count, v.as_ptr()); }
+            _ => panic!("uniforms have 1 to 4 components, not {}", components),
+        }
+    }
+
+    fn uniform_u32(&mut self, location: i32, components: usize, v: &[u32]) {
+        let count = (v.len() / components) as i32;
+        match components {
+            1 => unsafe { gl::Uniform1uiv(location, count, v.as_ptr()); }
+            2 => unsafe { gl::Uniform2uiv(location, count, v.as_ptr()); }
+            3 => unsafe { gl::Uniform3uiv(location, count, v.as_ptr()); }
+            4 => unsafe { gl::Uniform4uiv(location, count, v.as_ptr()); }
+            _ => panic!("uniforms have 1 to 4 components, not {}", components),
+        }
+    }
+
+    fn uniform_matrix(&mut self, location: i32, dimension: usize, v: &[f32]) {
+        let count = (v.len() / (dimension * dimension)) as i32;
+        match dimension {
+            2 => unsafe { gl::UniformMatrix2fv(location, count, gl::FALSE, v.as_ptr()); }
+            3 => unsafe { gl::UniformMatrix3fv(location, count, gl::FALSE, v.as_ptr()); }
+            4 => unsafe { gl::UniformMatrix4fv(location, count, gl::FALSE, v.as_ptr()); }
+            _ => panic!("matrix uniforms are 2x2 to 4x4, not {0}x{0}", dimension),
+        }
+    }
+
+    fn uniform_block_index(&mut self, program: u32, name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        match unsafe { gl::GetUniformBlockIndex(program, name.as_ptr()) } {
+            gl::INVALID_INDEX => None,
+            index => Some(index),
+        }
+    }
+
+    fn uniform_block_binding(&mut self, program: u32, block: u32, binding: u32) {
+        unsafe { gl::UniformBlockBinding(program, block, binding); }
+    }
+
+    fn create_vertex_array(&mut self) -> u32 {
+        let mut handler = 0;
+        unsafe { gl::GenVertexArrays(1, &mut handler); }
+        handler
+    }
+
+    fn bind_vertex_array(&mut self, vertex_array: u32) {
+        unsafe { gl::BindVertexArray(vertex_array); }
+    }
+
+    fn delete_vertex_array(&mut self, vertex_array: u32) {
+        unsafe { gl::DeleteVertexArrays(1, &vertex_array); }
+    }
+
+    fn active_attributes(&mut self, program: u32) -> Vec<ActiveAttribute> {
+        let mut count = 0;
+        let mut max_length = 0;
+        unsafe { gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count); }
+        unsafe { gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length); }
+
+        (0..count as u32).map(|index| {
+            let mut name = vec![0u8; max_length.max(1) as usize];
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            unsafe { gl::GetActiveAttrib(program, index, name.len() as i32, &mut length, &mut size, &mut gl_type, name.as_mut_ptr() as *mut i8); }
+            name.truncate(length as usize);
+
+            let name = String::from_utf8_lossy(&name).into_owned();
+            let location = CString::new(name.as_str()).ok().and_then(|c_name| match unsafe { gl::GetAttribLocation(program, c_name.as_ptr()) } {
+                -1 => None,
+                location => Some(location as u32),
+            });
+
+            ActiveAttribute { name, gl_type, size: size as usize, location }
+        }).collect()
+    }
+
+    fn vertex_attribute_pointer(&mut self, index: u32, attribute: &VertexAttribute) {
+        let attribute_type = attribute.attribute_type.gl_enum();
+        let normalized = match attribute.normalized {
+            true => gl::TRUE,
+            false => gl::FALSE,
+        };
+
+        // Integers only reach integer inputs through the I variant, the
+        // other one converts them to floats (packed formats always are).
+        if attribute.is_integer() {
+            unsafe {
+                gl::VertexAttribIPointer(
+                    index,
+                    attribute.size as i32,
+                    attribute_type,
+                    attribute.stride as i32,
+                    attribute.width as *const c_void,
+                );
+            }
+        } else {
+            unsafe {
+                gl::VertexAttribPointer(
+                    index,
+                    attribute.size as i32,
+                    attribute_type,
+                    normalized,
+                    attribute.stride as i32,
+                    attribute.width as *const c_void,
+                );
+            }
+        }
+    }
+
+    fn enable_vertex_attribute(&mut self, index: u32) {
+        unsafe { gl::EnableVertexAttribArray(index); }
+    }
+
+    fn vertex_attribute_divisor(&mut self, index: u32, divisor: u32) {
+        unsafe { gl::VertexAttribDivisor(index, divisor); }
+    }
+
+    fn draw_elements(&mut self, count: usize, first: usize, base_vertex: usize) {
+        let offset = (first * std::mem::size_of::<u32>()) as *const c_void;
+        unsafe { gl::DrawElementsBaseVertex(gl::TRIANGLES, count as i32, gl::UNSIGNED_INT, offset, base_vertex as i32); }
+    }
+
+    fn draw_elements_instanced(&mut self, count: usize, instances: usize) {
+        unsafe { gl::DrawElementsInstanced(gl::TRIANGLES, count as i32, gl::UNSIGNED_INT, null(), instances as i32); }
+    }
+
+    fn create_framebuffer(&mut self) -> u32 {
+        let mut handler = 0;
+        unsafe { gl::GenFramebuffers(1, &mut handler); }
+        handler
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: u32) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer); }
+    }
+
+    fn bound_framebuffer(&mut self) -> u32 {
+        let mut framebuffer = 0;
+        unsafe { gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer); }
+        framebuffer as u32
+    }
+
+    fn framebuffer_texture(&mut self, attachment: u32, texture: u32) {
+        unsafe { gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture, 0); }
+    }
+
+    fn framebuffer_renderbuffer(&mut self, attachment: u32, renderbuffer: u32) {
+        unsafe { gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer); }
+    }
+
+    fn check_framebuffer_status(&mut self) -> u32 {
+        unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) }
+    }
+
+    fn delete_framebuffer(&mut self, framebuffer: u32) {
+        unsafe { gl::DeleteFramebuffers(1, &framebuffer); }
+    }
+
+    fn create_renderbuffer(&mut self, internal_format: u32, width: u32, height: u32) -> u32 {
+        let mut handler = 0;
+        unsafe { gl::GenRenderbuffers(1, &mut handler); }
+        unsafe { gl::BindRenderbuffer(gl::RENDERBUFFER, handler); }
+        unsafe { gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width as i32, height as i32); }
+        handler
+    }
+
+    fn delete_renderbuffer(&mut self, renderbuffer: u32) {
+        unsafe { gl::DeleteRenderbuffers(1, &renderbuffer); }
+    }
+
+    fn viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe { gl::Viewport(x, y, width, height); }
+    }
+
+    fn get_viewport(&mut self) -> [i32; 4] {
+        let mut viewport = [0; 4];
+        unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()); }
+        viewport
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        self.depth_clears += 1;
+        unsafe { gl::ClearColor(color[0], color[1], color[2], color[3]); }
+        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT); }
+    }
+
+    fn clear_color(&mut self, color: [f32; 4]) {
+        unsafe { gl::ClearColor(color[0], color[1], color[2], color[3]); }
+        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT); }
+    }
+
+    fn clear_depth(&mut self) {
+        self.depth_clears += 1;
+        unsafe { gl::ClearDepth(1.); }
+        unsafe { gl::Clear(gl::DEPTH_BUFFER_BIT); }
+    }
+
+    fn depth_clears(&mut self) -> u64 {
+        self.depth_clears
+    }
+
+    fn depth_test(&mut self, enabled: bool) {
+        if enabled {
+            unsafe { gl::Enable(gl::DEPTH_TEST); }
+            unsafe { gl::DepthFunc(gl::LESS); }
+        } else {
+            unsafe { gl::Disable(gl::DEPTH_TEST); }
+        }
+    }
+
+    fn get_depth_test(&mut self) -> bool {
+        unsafe { gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE }
+    }
+
+    fn depth_write(&mut self, enabled: bool) {
+        unsafe { gl::DepthMask(if enabled { gl::TRUE } else { gl::FALSE }); }
+    }
+
+    fn get_depth_write(&mut self) -> bool {
+        let mut enabled = gl::FALSE;
+        unsafe { gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut enabled); }
+        enabled == gl::TRUE
+    }
+
+    fn blend(&mut self, enabled: bool) {
+        if enabled {
+            unsafe { gl::Enable(gl::BLEND); }
+            unsafe { gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA); }
+        } else {
+            unsafe { gl::Disable(gl::BLEND); }
+        }
+    }
+
+    fn cull(&mut self, enabled: bool) {
+        if enabled {
+            unsafe { gl::Enable(gl::CULL_FACE); }
+            unsafe { gl::CullFace(gl::BACK); }
+        } else {
+            unsafe { gl::Disable(gl::CULL_FACE); }
+        }
+    }
+
+    fn has_depth(&mut self) -> bool {
+        // The default framebuffer names its buffers differently.
+        let attachment = match self.bound_framebuffer() {
+            0 => gl::DEPTH,
+            _ => gl::DEPTH_ATTACHMENT,
+        };
+        let mut object_type = 0;
+        unsafe { gl::GetFramebufferAttachmentParameteriv(gl::DRAW_FRAMEBUFFER, attachment, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE, &mut object_type); }
+        object_type as u32 != gl::NONE
+    }
+
+    fn read_pixels(&mut self, x: i32, y: i32, width: u32, height: u32, pixels: &mut [u8]) {
+        unsafe { gl::PixelStorei(gl::PACK_ALIGNMENT, 1); }
+        unsafe { gl::ReadPixels(x, y, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void); }
+    }
+
+    fn blit_framebuffer(&mut self, source: u32, source_rect: [i32; 4], destination: u32, destination_rect: [i32; 4], filter: u32) {
+        let [x0, y0, x1, y1] = source_rect;
+        let [dx0, dy0, dx1, dy1] = destination_rect;
+        unsafe { gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source); }
+        unsafe { gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, destination); }
+        unsafe { gl::BlitFramebuffer(x0, y0, x1, y1, dx0, dy0, dx1, dy1, gl::COLOR_BUFFER_BIT, filter); }
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, destination); }
+    }
+
+    fn fence_sync(&mut self) -> usize {
+        unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) as usize }
+    }
+
+    fn wait_sync(&mut self, fence: usize) {
+        loop {
+            let status = unsafe { gl::ClientWaitSync(fence as gl::types::GLsync, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000) };
+            if status != gl::TIMEOUT_EXPIRED {
+                break;
+            }
+        }
+    }
+
+    fn delete_sync(&mut self, fence: usize) {
+        unsafe { gl::DeleteSync(fence as gl::types::GLsync); }
+    }
+
+    fn finish(&mut self) {
+        unsafe { gl::Finish(); }
+    }
+
+    fn get_error(&mut self) -> u32 {
+        unsafe { gl::GetError() }
+    }
+}
+
+/// A call received by [`RecordingDevice`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    CreateBuffer(u32),
+    BindBuffer { target: u32, buffer: u32 },
+    BufferData { target: u32, data: Vec<u8>, usage: u32 },
+    AllocateBuffer { target: u32, size: usize, usage: u32 },
+    BufferSubData { target: u32, offset: usize, data: Vec<u8> },
+    WriteMappedBuffer { target: u32, offset: usize, data: Vec<u8>, access: u32 },
+    DeleteBuffer(u32),
+    BindBufferBase { target: u32, index: u32, buffer: u32 },
+
+    CreateTexture(u32),
+    ActiveTexture(u32),
+    BindTexture(u32),
+    TextureParameter { parameter: u32, value: i32 },
+    TextureParameterF32 { parameter: u32, values: Vec<f32> },
+    TextureImage2D { internal_format: u32, width: u32, height: u32, format: u32 },
+    TextureSubImage2D { x: u32, y: u32, width: u32, height: u32, format: u32 },
+    GenerateMipmap,
+    DeleteTexture(u32),
+
+    CreateShader { shader_type: u32, shader: u32 },
+    CompileShader { shader: u32, source: String },
+    DeleteShader(u32),
+
+    CreateProgram(u32),
+    AttachShader { program: u32, shader: u32 },
+    LinkProgram(u32),
+    UseProgram(u32),
+    DeleteProgram(u32),
+
+    UniformF32 { location: i32, components: usize, v: Vec<f32> },
+    UniformI32 { location: i32, components: usize, v: Vec<i32> },
+    UniformU32 { location: i32, components: usize, v: Vec<u32> },
+    UniformMatrix { location: i32, dimension: usize, v: Vec<f32> },
+    UniformBlockBinding { program: u32, block: u32, binding: u32 },
+
+    CreateVertexArray(u32),
+    BindVertexArray(u32),
+    DeleteVertexArray(u32),
+    VertexAttributePointer { index: u32, size: usize, stride: usize, offset: usize },
+    EnableVertexAttribute(u32),
+    VertexAttributeDivisor { index: u32, divisor: u32 },
+
+    DrawElements { count: usize, first: usize, base_vertex: usize },
+    DrawElementsInstanced { count: usize, instances: usize },
+
+    CreateFramebuffer(u32),
+    BindFramebuffer(u32),
+    FramebufferTexture { attachment: u32, texture: u32 },
+    FramebufferRenderbuffer { attachment: u32, renderbuffer: u32 },
+    DeleteFramebuffer(u32),
+    CreateRenderbuffer { renderbuffer: u32, internal_format: u32, width: u32, height: u32 },
+    DeleteRenderbuffer(u32),
+    Viewport { x: i32, y: i32, width: i32, height: i32 },
+    Clear([f32; 4]),
+    ClearColor([f32; 4]),
+    ClearDepth,
+    DepthTest(bool),
+    DepthWrite(bool),
+    Blend(bool),
+    Cull(bool),
+    ReadPixels { x: i32, y: i32, width: u32, height: u32 },
+    BlitFramebuffer { source: u32, source_rect: [i32; 4], destination: u32, destination_rect: [i32; 4], filter: u32 },
+
+    FenceSync(usize),
+    WaitSync(usize),
+    DeleteSync(usize),
+    Finish,
+}
+
+/// Device that needs no OpenGL context. It hands out fresh handles, reports
+/// every shader and program as valid and appends each call to a shared log,
+/// so rendering code can be inspected in tests. The default framebuffer is
+/// reported to have a depth buffer.
+pub struct RecordingDevice {
+    log: Rc<RefCell<Vec<Command>>>,
+    next_handler: u32,
+    uniforms: Vec<(u32, String)>,
+    /// Reported by every program, `None` to give any uniform a location.
+    active_uniforms: Option<Vec<ActiveUniform>>,
+    /// Vertex inputs reported by every program.
+    active_attributes: Vec<ActiveAttribute>,
+    texture_units: u32,
+    framebuffer: u32,
+    /// Framebuffers given a depth attachment.
+    depth_framebuffers: Vec<u32>,
+    viewport: [i32; 4],
+    depth_test: bool,
+    depth_write: bool,
+    depth_clears: u64,
+}
+
+impl RecordingDevice {
+    pub fn new() -> Self {
+        Self {
+            log: Rc::new(RefCell::new(Vec::new())),
+            next_handler: 1,
+            uniforms: Vec::new(),
+            active_uniforms: None,
+            active_attributes: Vec::new(),
+            texture_units: 16,
+            framebuffer: 0,
+            depth_framebuffers: Vec::new(),
+            viewport: [0; 4],
+            depth_test: false,
+            depth_write: true,
+            depth_clears: 0,
+        }
+    }
+
+    /// Makes the device report `units` texture image units instead of 16.
+    pub fn with_texture_units(mut self, units: u32) -> Self {
+        self.texture_units = units;
+        self
+    }
+
+    /// Makes every program report `uniforms` as its active ones, and only
+    /// those have a location.
+    pub fn with_active_uniforms(mut self, uniforms: Vec<ActiveUniform>) -> Self {
+        self.active_uniforms = Some(uniforms);
+        self
+    }
+
+    /// Makes every program report `attributes` as its vertex inputs, which
+    /// are none by default.
+    pub fn with_active_attributes(mut self, attributes: Vec<ActiveAttribute>) -> Self {
+        self.active_attributes = attributes;
+        self
+    }
+
+    /// Log shared with the device, still readable after the device is handed to [`set`].
+    pub fn log(&self) -> Rc<RefCell<Vec<Command>>> {
+        self.log.clone()
+    }
+
+    fn record(&mut self, command: Command) {
+        self.log.borrow_mut().push(command);
+    }
+
+    fn next_handler(&mut self) -> u32 {
+        let handler = self.next_handler;
+        self.next_handler += 1;
+        handler
+    }
+}
+
+impl Default for RecordingDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderDevice for RecordingDevice {
+    fn create_buffer(&mut self) -> u32 {
+        let handler = self.next_handler();
+        self.record(Command::CreateBuffer(handler));
+        handler
+    }
+
+    fn bind_buffer(&mut self, target: u32, buffer: u32) {
+        self.record(Command::BindBuffer { target, buffer });
+    }
+
+    fn buffer_data(&mut self, target: u32, data: &[u8], usage: u32) {
+        self.record(Command::BufferData { target, data: data.to_vec(), usage });
+    }
+
+    fn allocate_buffer(&mut self, target: u32, size: usize, usage: u32) {
+        self.record(Command::AllocateBuffer { target, size, usage });
+    }
+
+    fn buffer_sub_data(&mut self, target: u32, offset: usize, data: &[u8]) {
+        self.record(Command::BufferSubData { target, offset, data: data.to_vec() });
+    }
+
+    fn write_mapped_buffer(&mut self, target: u32, offset: usize, data: &[u8], access: u32) {
+        self.record(Command::WriteMappedBuffer { target, offset, data: data.to_vec(), access });
+    }
+
+    fn delete_buffer(&mut self, buffer: u32) {
+        self.record(Command::DeleteBuffer(buffer));
+    }
+
+    fn bind_buffer_base(&mut self, target: u32, index: u32, buffer: u32) {
+        self.record(Command::BindBufferBase { target, index, buffer });
+    }
+
+    fn create_texture(&mut self) -> u32 {
+        let handler = self.next_handler();
+        self.record(Command::CreateTexture(handler));
+        handler
+    }
+
+    fn active_texture(&mut self, slot: u32) {
+        self.record(Command::ActiveTexture(slot));
+    }
+
+    fn bind_texture(&mut self, texture: u32) {
+        self.record(Command::BindTexture(texture));
+    }
+
+    fn texture_parameter(&mut self, parameter: u32, value: i32) {
+        self.record(Command::TextureParameter { parameter, value });
+    }
+
+    fn texture_parameter_f32(&mut self, parameter: u32, values: &[f32]) {
+        self.record(Command::TextureParameterF32 { parameter, values: values.to_vec() });
+    }
+
+    fn texture_image_2d(&mut self, internal_format: u32, width: u32, height: u32, format: u32, _pixels: &[u8]) {
+        self.record(Command::TextureImage2D { internal_format, width, height, format });
+    }
+
+    fn texture_sub_image_2d(&mut self, x: u32, y: u32, width: u32, height: u32, format: u32, _pixels: &[u8]) {
+        self.record(Command::TextureSubImage2D { x, y, width, height, format });
+    }
+
+    fn generate_mipmap(&mut self) {
+        self.record(Command::GenerateMipmap);
+    }
+
+    fn delete_texture(&mut self, texture: u32) {
+        self.record(Command::DeleteTexture(texture));
+    }
+
+    fn max_texture_image_units(&mut self) -> u32 {
+        self.texture_units
+    }
+
+    fn max_anisotropy(&mut self) -> f32 {
+        16.
+    }
+
+    fn create_shader(&mut self, shader_type: u32) -> u32 {
+        let shader = self.next_handler();
+        self.record(Command::CreateShader { shader_type, shader });
+        shader
+    }
+
+    fn compile_shader(&mut self, shader: u32, source: &str) -> bool {
+        self.record(Command::CompileShader { shader, source: source.to_string() });
+        true
+    }
+
+    fn shader_info_log(&mut self, _shader: u32) -> String {
+        String::new()
+    }
+
+    fn delete_shader(&mut self, shader: u32) {
+        self.record(Command::DeleteShader(shader));
+    }
+
+    fn create_program(&mut self) -> u32 {
+        let handler = self.next_handler();
+        self.record(Command::CreateProgram(handler));
+        handler
+    }
+
+    fn attach_shader(&mut self, program: u32, shader: u32) {
+        self.record(Command::AttachShader { program, shader });
+    }
+
+    fn link_program(&mut self, program: u32) -> bool {
+        self.record(Command::LinkProgram(program));
+        true
+    }
+
+    fn program_info_log(&mut self, _program: u32) -> String {
+        String::new()
+    }
+
+    fn use_program(&mut self, program: u32) {
+        self.record(Command::UseProgram(program));
+    }
+
+    fn delete_program(&mut self, program: u32) {
+        self.record(Command::DeleteProgram(program));
+    }
+
+    fn uniform_location(&mut self, program: u32, name: &str) -> Option<i32> {
+        if let Some(active) = &self.active_uniforms {
+            return active.iter().find(|uniform| uniform.name == name)?.location;
+        }
+
+        let uniform = (program, name.to_string());
+        let location = match self.uniforms.iter().position(|u| *u == uniform) {
+            Some(location) => location,
+            None => {
+                self.uniforms.push(uniform);
+                self.uniforms.len() - 1
+            }
+        };
+        Some(location as i32)
+    }
+
+    fn active_uniforms(&mut self, _program: u32) -> Vec<ActiveUniform> {
+        self.active_uniforms.clone().unwrap_or_default()
+    }
+
+    fn uniform_f32(&mut self, location: i32, components: usize, v: &[f32]) {
+        self.record(Command::UniformF32 { location, components, v: v.to_vec() });
+    }
+
+    fn uniform_i32(&mut self, location: i32, components: usize, v: &[i32]) {
+        self.record(Command::UniformI32 { location, components, v: v.to_vec() });
+    }
+
+    fn uniform_u32(&mut self, location: i32, components: usize, v: &[u32]) {
+        self.record(Command::UniformU32 { location, components, v: v.to_vec() });
+    }
+
+    fn uniform_matrix(&mut self, location: i32, dimension: usize, v: &[f32]) {
+        self.record(Command::UniformMatrix { location, dimension, v: v.to_vec() });
+    }
+
+    fn uniform_block_index(&mut self, program: u32, name: &str) -> Option<u32> {
+        self.uniform_location(program, name).map(|index| index as u32)
+    }
+
+    fn uniform_block_binding(&mut self, program: u32, block: u32, binding: u32) {
+        self.record(Command::UniformBlockBinding { program, block, binding });
+    }
+
+    fn create_vertex_array(&mut self) -> u32 {
+        let handler = self.next_handler();
+        self.record(Command::CreateVertexArray(handler));
+        handler
+    }
+
+    fn bind_vertex_array(&mut self, vertex_array: u32) {
+        self.record(Command::BindVertexArray(vertex_array));
+    }
+
+    fn delete_vertex_array(&mut self, vertex_array: u32) {
+        self.record(Command::DeleteVertexArray(vertex_array));
+    }
+
+    fn active_attributes(&mut self, _program: u32) -> Vec<ActiveAttribute> {
+        self.active_attributes.clone()
+    }
+
+    fn vertex_attribute_pointer(&mut self, index: u32, attribute: &VertexAttribute) {
+        self.record(Command::VertexAttributePointer {
+            index,
+            size: attribute.size,
+            stride: attribute.stride,
+            offset: attribute.width,
+        });
+    }
+
+    fn enable_vertex_attribute(&mut self, index: u32) {
+        self.record(Command::EnableVertexAttribute(index));
+    }
+
+    fn vertex_attribute_divisor(&mut self, index: u32, divisor: u32) {
+        self.record(Command::VertexAttributeDivisor { index, divisor });
+    }
+
+    fn draw_elements(&mut self, count: usize, first: usize, base_vertex: usize) {
+        self.record(Command::DrawElements { count, first, base_vertex });
+    }
+
+    fn draw_elements_instanced(&mut self, count: usize, instances: usize) {
+        self.record(Command::DrawElementsInstanced { count, instances });
+    }
+
+    fn create_framebuffer(&mut self) -> u32 {
+        let framebuffer = self.next_handler();
+        self.record(Command::CreateFramebuffer(framebuffer));
+        framebuffer
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: u32) {
+        self.framebuffer = framebuffer;
+        self.record(Command::BindFramebuffer(framebuffer));
+    }
+
+    fn bound_framebuffer(&mut self) -> u32 {
+        self.framebuffer
+    }
+
+    fn framebuffer_texture(&mut self, attachment: u32, texture: u32) {
+        self.record(Command::FramebufferTexture { attachment, texture });
+    }
+
+    fn framebuffer_renderbuffer(&mut self, attachment: u32, renderbuffer: u32) {
+        if attachment == gl::DEPTH_ATTACHMENT || attachment == gl::DEPTH_STENCIL_ATTACHMENT {
+            self.depth_framebuffers.push(self.framebuffer);
+        }
+        self.record(Command::FramebufferRenderbuffer { attachment, renderbuffer });
+    }
+
+    fn check_framebuffer_status(&mut self) -> u32 {
+        gl::FRAMEBUFFER_COMPLETE
+    }
+
+    fn delete_framebuffer(&mut self, framebuffer: u32) {
+        self.record(Command::DeleteFramebuffer(framebuffer));
+    }
+
+    fn create_renderbuffer(&mut self, internal_format: u32, width: u32, height: u32) -> u32 {
+        let renderbuffer = self.next_handler();
+        self.record(Command::CreateRenderbuffer { renderbuffer, internal_format, width, height });
+        renderbuffer
+    }
+
+    fn delete_renderbuffer(&mut self, renderbuffer: u32) {
+        self.record(Command::DeleteRenderbuffer(renderbuffer));
+    }
+
+    fn viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport = [x, y, width, height];
+        self.record(Command::Viewport { x, y, width, height });
+    }
+
+    fn get_viewport(&mut self) -> [i32; 4] {
+        self.viewport
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        self.depth_clears += 1;
+        self.record(Command::Clear(color));
+    }
+
+    fn clear_color(&mut self, color: [f32; 4]) {
+        self.record(Command::ClearColor(color));
+    }
+
+    fn clear_depth(&mut self) {
+        self.depth_clears += 1;
+        self.record(Command::ClearDepth);
+    }
+
+    fn depth_clears(&mut self) -> u64 {
+        self.depth_clears
+    }
+
+    fn depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+        self.record(Command::DepthTest(enabled));
+    }
+
+    fn get_depth_test(&mut self) -> bool {
+        self.depth_test
+    }
+
+    fn depth_write(&mut self, enabled: bool) {
+        self.depth_write = enabled;
+        self.record(Command::DepthWrite(enabled));
+    }
+
+    fn get_depth_write(&mut self) -> bool {
+        self.depth_write
+    }
+
+    fn blend(&mut self, enabled: bool) {
+        self.record(Command::Blend(enabled));
+    }
+
+    fn cull(&mut self, enabled: bool) {
+        self.record(Command::Cull(enabled));
+    }
+
+    fn has_depth(&mut self) -> bool {
+        self.framebuffer == 0 || self.depth_framebuffers.contains(&self.framebuffer)
+    }
+
+    fn read_pixels(&mut self, x: i32, y: i32, width: u32, height: u32, _pixels: &mut [u8]) {
+        self.record(Command::ReadPixels { x, y, width, height });
+    }
+
+    fn blit_framebuffer(&mut self, source: u32, source_rect: [i32; 4], destination: u32, destination_rect: [i32; 4], filter: u32) {
+        self.framebuffer = destination;
+        self.record(Command::BlitFramebuffer { source, source_rect, destination, destination_rect, filter });
+    }
+
+    fn fence_sync(&mut self) -> usize {
+        let fence = self.next_handler() as usize;
+        self.record(Command::FenceSync(fence));
+        fence
+    }
+
+    fn wait_sync(&mut self, fence: usize) {
+        self.record(Command::WaitSync(fence));
+    }
+
+    fn delete_sync(&mut self, fence: usize) {
+        self.record(Command::DeleteSync(fence));
+    }
+
+    fn finish(&mut self) {
+        self.record(Command::Finish);
+    }
+
+    fn get_error(&mut self) -> u32 {
+        gl::NO_ERROR
+    }
+}
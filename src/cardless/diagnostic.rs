@@ -0,0 +1,232 @@
+use std::{fmt, path::PathBuf};
+
+use super::shader::ShaderType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// One message of a shader info log, tied to the source line it blames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderType,
+    /// File the source was read from, if any.
+    pub file: Option<PathBuf>,
+    pub severity: Severity,
+    /// 1-based line in the source given to the compiler.
+    pub line: Option<usize>,
+    /// 1-based column, only reported by some drivers.
+    pub column: Option<usize>,
+    pub message: String,
+    pub source_line: Option<String>,
+}
+
+/// Splits a compile log into diagnostics. Understands the formats of
+/// Mesa (`0:12(5): error: ...`), NVIDIA (`0(12) : error C0000: ...`) and
+/// AMD/Intel/Apple (`ERROR: 0:12: ...`); other non-empty lines are kept
+/// as diagnostics without a location.
+pub fn parse_log(stage: ShaderType, log: &str, source: &str) -> Vec<ShaderDiagnostic> {
+    let source_lines: Vec<&str> = source.lines().collect();
+
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (severity, location, message) = match parse_line(line) {
+                Some((severity, location, message)) => (severity, Some(location), message),
+                None => (guess_severity(line), None, line.to_string()),
+            };
+            let line = location.map(|(line, _)| line);
+            let column = location.and_then(|(_, column)| column);
+            let source_line = line
+                .and_then(|line| line.checked_sub(1))
+                .and_then(|line| source_lines.get(line))
+                .map(|line| line.to_string());
+
+            ShaderDiagnostic { stage, file: None, severity, line, column, message, source_line }
+        })
+        .collect()
+}
+
+type Location = (usize, Option<usize>);
+
+fn parse_line(line: &str) -> Option<(Severity, Location, String)> {
+    parse_mesa(line)
+        .or_else(|| parse_nvidia(line))
+        .or_else(|| parse_prefixed(line))
+}
+
+// 0:12(5): error: message
+fn parse_mesa(line: &str) -> Option<(Severity, Location, String)> {
+    let (_, rest) = take_number(line)?;
+    let rest = rest.strip_prefix(':')?;
+    let (line, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix('(')?;
+    let (column, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix("):")?;
+    let (severity, message) = take_severity(rest)?;
+
+    Some((severity, (line, Some(column)), message))
+}
+
+// 0(12) : error C0000: message
+fn parse_nvidia(line: &str) -> Option<(Severity, Location, String)> {
+    let (_, rest) = take_number(line)?;
+    let rest = rest.strip_prefix('(')?;
+    let (line, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+    let (severity, message) = take_severity(rest)?;
+
+    Some((severity, (line, None), message))
+}
+
+// ERROR: 0:12: message
+fn parse_prefixed(line: &str) -> Option<(Severity, Location, String)> {
+    let (severity, rest) = line.split_once(':')?;
+    let severity = severity_from_word(severity)?;
+    let (_, rest) = take_number(rest.trim_start())?;
+    let rest = rest.strip_prefix(':')?;
+    let (line, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(':')?;
+
+    Some((severity, (line, None), rest.trim().to_string()))
+}
+
+fn take_number(s: &str) -> Option<(usize, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let number = s[..end].parse().ok()?;
+    Some((number, &s[end..]))
+}
+
+// " error C0000: message" or " error: message"
+fn take_severity(s: &str) -> Option<(Severity, String)> {
+    let (head, message) = s.split_once(':')?;
+    let word = head.split_whitespace().next()?;
+    let severity = severity_from_word(word)?;
+    Some((severity, message.trim().to_string()))
+}
+
+fn severity_from_word(word: &str) -> Option<Severity> {
+    match word.trim().to_ascii_lowercase().as_str() {
+        "error" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "note" | "info" => Some(Severity::Note),
+        _ => None,
+    }
+}
+
+fn guess_severity(line: &str) -> Severity {
+    let line = line.to_ascii_lowercase();
+    if line.contains("error") {
+        Severity::Error
+    } else if line.contains("warning") {
+        Severity::Warning
+    } else {
+        Severity::Note
+    }
+}
+
+/// Renders the diagnostic the way rustc does:
+///
+/// ```text
+/// error: 'foo' undeclared
+///   --> fragment shader:12:5
+///    |
+/// 12 |     foo = 1.0;
+///    |     ^^^^^^^^^^
+/// ```
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+
+        let line = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+
+        let gutter = " ".repeat(line.to_string().len());
+        let origin = match &self.file {
+            Some(file) => file.display().to_string(),
+            None => format!("{} shader", self.stage),
+        };
+        match self.column {
+            Some(column) => writeln!(f, "{}--> {}:{}:{}", gutter, origin, line, column)?,
+            None => writeln!(f, "{}--> {}:{}", gutter, origin, line)?,
+        }
+
+        let source_line = match &self.source_line {
+            Some(source_line) => source_line,
+            None => return Ok(()),
+        };
+
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line, source_line)?;
+
+        // Columns differ too much between drivers to point at, so the whole
+        // statement is underlined.
+        let indent = source_line.len() - source_line.trim_start().len();
+        let width = source_line.trim().len().max(1);
+        writeln!(f, "{} | {}{}", gutter, " ".repeat(indent), "^".repeat(width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cardless::shader::ShaderType;
+
+    use super::{parse_log, Severity};
+
+    const SOURCE: &str = "#version 330 core\nvoid main() {\n    gl_Position = foo;\n}\n";
+
+    #[test]
+    fn parses_driver_formats() {
+        let logs = [
+            "0:3(19): error: `foo' undeclared",
+            "0(3) : error C1008: undefined variable \"foo\"",
+            "ERROR: 0:3: 'foo' : undeclared identifier",
+        ];
+
+        for log in logs.iter() {
+            let diagnostics = parse_log(ShaderType::VERTEX, log, SOURCE);
+            assert_eq!(diagnostics.len(), 1, "{}", log);
+            assert_eq!(diagnostics[0].severity, Severity::Error);
+            assert_eq!(diagnostics[0].line, Some(3));
+            assert_eq!(diagnostics[0].source_line.as_deref(), Some("    gl_Position = foo;"));
+        }
+    }
+
+    #[test]
+    fn keeps_unrecognised_lines() {
+        let diagnostics = parse_log(ShaderType::FRAGMENT, "\nLink failed because of a warning\n", SOURCE);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].line, None);
+    }
+
+    #[test]
+    fn renders_source_excerpt() {
+        let diagnostics = parse_log(ShaderType::VERTEX, "0:3(19): error: `foo' undeclared", SOURCE);
+
+        assert_eq!(diagnostics[0].to_string(), "\
+error: `foo' undeclared
+ --> vertex shader:3:19
+  |
+3 |     gl_Position = foo;
+  |     ^^^^^^^^^^^^^^^^^^
+");
+    }
+}
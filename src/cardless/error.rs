@@ -0,0 +1,151 @@
+use std::{error::Error, fmt, path::PathBuf};
+
+use super::{buffer::StreamingStrategy, device, diagnostic::ShaderDiagnostic, shader::ShaderType};
+
+#[derive(Debug)]
+pub enum CardlessError {
+    /// Shader failed to compile, `log` is the driver's info log and
+    /// `diagnostics` its messages matched with the offending source lines.
+    Compile { stage: ShaderType, log: String, diagnostics: Vec<ShaderDiagnostic> },
+    /// Shader source could not be preprocessed, `file` is the include
+    /// the faulty line is in, `None` for the top-level source.
+    Preprocess { file: Option<PathBuf>, line: usize, message: String },
+    /// Shader program failed to link, `log` is the driver's info log.
+    Link { log: String },
+    /// Shader program linked, but its vertex input `name` does not match
+    /// what the vertex layout puts at `location`.
+    AttributeMismatch { name: String, location: u32, message: String },
+    /// The vertex layout entry at `location` cannot be described to OpenGL.
+    Layout { location: u32, message: String },
+    /// Image `name` is larger than the pages of the atlas it was added to.
+    AtlasOverflow { name: String, width: u32, height: u32, page_size: u32 },
+    /// An image named `name` was already added to the atlas.
+    DuplicateRegion(String),
+    ImageDecode(image::ImageError),
+    /// Pixel data of `actual` bytes was given where `expected` were needed.
+    InvalidPixelData { expected: usize, actual: usize },
+    /// The `width` x `height` region at `x`, `y` does not fit in a
+    /// `texture_width` x `texture_height` texture.
+    RegionOutOfBounds { x: u32, y: u32, width: u32, height: u32, texture_width: u32, texture_height: u32 },
+    /// `glCheckFramebufferStatus` returned `status` for a new framebuffer.
+    IncompleteFramebuffer(u32),
+    /// A LUT cannot have `size` levels per channel: fewer than 2, or too many
+    /// for its width to fit in a `u32`.
+    InvalidLutSize(u32),
+    /// Asset bundle data is malformed.
+    Bundle(String),
+    /// Instances cannot be streamed with `strategy`, GL 3.3 draws having no
+    /// base instance to start from an offset.
+    InstanceStreaming(StreamingStrategy),
+    Io(std::io::Error),
+    /// Reading an asset from `path` failed.
+    File { path: PathBuf, error: std::io::Error },
+    /// `glGetError` reported `code` right after `operation`.
+    Gl { operation: &'static str, code: u32 },
+    /// No OpenGL context could be created.
+    Context(String),
+    /// Golden image `path` does not exist yet.
+    MissingGolden(PathBuf),
+    /// Frame `name` differs from its golden image, the frame and a diff image
+    /// being saved to `actual` and `diff`.
+    GoldenMismatch { name: String, differing_pixels: usize, max_difference: u8, tolerance: u8, actual: PathBuf, diff: PathBuf },
+}
+
+impl fmt::Display for CardlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardlessError::Compile { stage, log, diagnostics } => {
+                writeln!(f, "failed to compile {} shader:", stage)?;
+                if diagnostics.is_empty() {
+                    return write!(f, "{}", log);
+                }
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            CardlessError::Preprocess { file, line, message } => match file {
+                Some(file) => write!(f, "{}:{}: {}", file.display(), line, message),
+                None => write!(f, "shader source:{}: {}", line, message),
+            },
+            CardlessError::Link { log } => write!(f, "failed to link shader program:\n{}", log),
+            CardlessError::AttributeMismatch { name, location, message } => {
+                write!(f, "vertex attribute `{}` at location {} {}", name, location, message)
+            }
+            CardlessError::Layout { location, message } => write!(f, "vertex layout entry at location {} {}", location, message),
+            CardlessError::AtlasOverflow { name, width, height, page_size } => {
+                write!(f, "image `{}` ({}x{}) does not fit in a {}x{} atlas page", name, width, height, page_size, page_size)
+            }
+            CardlessError::DuplicateRegion(name) => write!(f, "atlas already has an image named `{}`", name),
+            CardlessError::InvalidPixelData { expected, actual } => {
+                write!(f, "expected {} bytes of pixel data, got {}", expected, actual)
+            }
+            CardlessError::RegionOutOfBounds { x, y, width, height, texture_width, texture_height } => write!(f,
+                "{}x{} region at {}, {} is outside the {}x{} texture", width, height, x, y, texture_width, texture_height),
+            CardlessError::InvalidLutSize(size) => write!(f, "a LUT cannot have {} levels per channel", size),
+            CardlessError::ImageDecode(e) => write!(f, "failed to decode image: {}", e),
+            CardlessError::IncompleteFramebuffer(status) => write!(f, "framebuffer is incomplete: 0x{:x}", status),
+            CardlessError::Bundle(message) => write!(f, "invalid asset bundle: {}", message),
+            CardlessError::InstanceStreaming(strategy) => write!(f, "instances cannot be streamed with {:?}", strategy),
+            CardlessError::Io(e) => write!(f, "{}", e),
+            CardlessError::File { path, error } => write!(f, "{}: {}", path.display(), error),
+            CardlessError::Gl { operation, code } => write!(f, "{} failed with {}", operation, gl_error_name(*code)),
+            CardlessError::Context(message) => write!(f, "failed to create OpenGL context: {}", message),
+            CardlessError::MissingGolden(path) => {
+                write!(f, "missing golden image {}, run with CARDLESS_BLESS=1 to create it", path.display())
+            }
+            CardlessError::GoldenMismatch { name, differing_pixels, max_difference, tolerance, actual, diff } => write!(
+                f,
+                "{}: {} pixels differ by up to {} (tolerance {}), see {} and {}",
+                name,
+                differing_pixels,
+                max_difference,
+                tolerance,
+                actual.display(),
+                diff.display(),
+            ),
+        }
+    }
+}
+
+impl Error for CardlessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CardlessError::ImageDecode(e) => Some(e),
+            CardlessError::Io(e) => Some(e),
+            CardlessError::File { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for CardlessError {
+    fn from(e: image::ImageError) -> Self {
+        CardlessError::ImageDecode(e)
+    }
+}
+
+impl From<std::io::Error> for CardlessError {
+    fn from(e: std::io::Error) -> Self {
+        CardlessError::Io(e)
+    }
+}
+
+/// Turns the pending `glGetError` value into an error blamed on `operation`.
+pub fn check_gl(operation: &'static str) -> Result<(), CardlessError> {
+    match device::with(|device| device.get_error()) {
+        gl::NO_ERROR => Ok(()),
+        code => Err(CardlessError::Gl { operation, code }),
+    }
+}
+
+fn gl_error_name(code: u32) -> String {
+    match code {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM".to_string(),
+        gl::INVALID_VALUE => "GL_INVALID_VALUE".to_string(),
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION".to_string(),
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION".to_string(),
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY".to_string(),
+        code => format!("GL error 0x{:x}", code),
+    }
+}
@@ -0,0 +1,137 @@
+//! Offscreen render targets. Draws issued inside [`Framebuffer::render`] end
+//! up in its colour texture, which can then be drawn like any other:
+//!
+//! ```ignore
+//! let target = Framebuffer::try_new(320, 180, false)?;
+//! target.render(|| {
+//!     target.clear([0., 0., 0., 1.]);
+//!     br.draw_sprite(&player, &player_texture);
+//!     br.flush();
+//! });
+//! br.push_square_texture(pos, size, &target.color);
+//! ```
+
+use super::{device, error::{CardlessError, check_gl}, texture::{Filter, Texture, TextureOptions, Wrap}};
+
+pub struct Framebuffer {
+    pub handler: u32,
+    /// Colour attachment, sampled linearly and clamped to the edges.
+    pub color: Texture,
+    /// Depth and stencil are only tested, never sampled, so a renderbuffer
+    /// holds them.
+    depth_stencil: Option<u32>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Framebuffer {
+    /// Framebuffer with an RGBA colour texture and, if `depth_stencil` is set,
+    /// a 24-bit depth and 8-bit stencil buffer.
+    pub fn try_new(width: u32, height: u32, depth_stencil: bool) -> Result<Self, CardlessError> {
+        let options = TextureOptions::new().with_filter(Filter::Linear).with_wrap(Wrap::Clamp).with_mipmaps(false);
+        let color = Texture::try_empty(width, height, &options)?;
+
+        let (handler, depth_stencil, status) = device::with(|device| {
+            let previous = device.bound_framebuffer();
+            let handler = device.create_framebuffer();
+            device.bind_framebuffer(handler);
+            device.framebuffer_texture(gl::COLOR_ATTACHMENT0, color.handler);
+
+            let depth_stencil = if depth_stencil {
+                let renderbuffer = device.create_renderbuffer(gl::DEPTH24_STENCIL8, width, height);
+                device.framebuffer_renderbuffer(gl::DEPTH_STENCIL_ATTACHMENT, renderbuffer);
+                Some(renderbuffer)
+            } else {
+                None
+            };
+
+            let status = device.check_framebuffer_status();
+            device.bind_framebuffer(previous);
+            (handler, depth_stencil, status)
+        });
+
+        let framebuffer = Self { handler, color, depth_stencil, width, height };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(CardlessError::IncompleteFramebuffer(status));
+        }
+        check_gl("framebuffer creation")?;
+
+        Ok(framebuffer)
+    }
+
+    /// Makes this the target of subsequent draws, covering all of it.
+    pub fn bind(&self) {
+        device::with(|device| {
+            device.bind_framebuffer(self.handler);
+            device.viewport(0, 0, self.width as i32, self.height as i32);
+        });
+    }
+
+    /// Runs `draw` with this framebuffer bound, then restores the previous
+    /// target and viewport.
+    pub fn render<R, F: FnOnce() -> R>(&self, draw: F) -> R {
+        let (previous, viewport) = device::with(|device| (device.bound_framebuffer(), device.get_viewport()));
+        self.bind();
+        let result = draw();
+        device::with(|device| {
+            device.bind_framebuffer(previous);
+            device.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        });
+        result
+    }
+
+    /// Clears every attachment, `color` going to the colour texture.
+    /// Expects the framebuffer to be bound.
+    pub fn clear(&self, color: [f32; 4]) {
+        device::with(|device| device.clear(color));
+    }
+
+    /// Copies the colour attachment into an image, the first row being the top.
+    pub fn read_pixels(&self) -> image::RgbaImage {
+        let mut pixels = vec![0; self.width as usize * self.height as usize * 4];
+        device::with(|device| {
+            let previous = device.bound_framebuffer();
+            device.bind_framebuffer(self.handler);
+            device.read_pixels(0, 0, self.width, self.height, &mut pixels);
+            device.bind_framebuffer(previous);
+        });
+
+        let image = image::RgbaImage::from_raw(self.width, self.height, pixels).unwrap();
+        image::imageops::flip_vertical(&image)
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        device::with(|device| {
+            device.delete_framebuffer(self.handler);
+            if let Some(renderbuffer) = self.depth_stencil {
+                device.delete_renderbuffer(renderbuffer);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cardless::device::{self, Command};
+
+    use super::Framebuffer;
+
+    #[test]
+    fn render_restores_the_previous_target() {
+        let log = device::record();
+        device::with(|device| device.viewport(0, 0, 800, 600));
+
+        let framebuffer = Framebuffer::try_new(64, 32, true).unwrap();
+        let drawn_into = framebuffer.render(|| device::with(|device| device.bound_framebuffer()));
+
+        assert_eq!(drawn_into, framebuffer.handler);
+        assert_eq!(device::with(|device| (device.bound_framebuffer(), device.get_viewport())), (0, [0, 0, 800, 600]));
+
+        let log = log.borrow();
+        assert!(log.contains(&Command::FramebufferTexture { attachment: gl::COLOR_ATTACHMENT0, texture: framebuffer.color.handler }));
+        assert!(log.contains(&Command::Viewport { x: 0, y: 0, width: 64, height: 32 }));
+        assert!(log.iter().any(|command| matches!(command, Command::CreateRenderbuffer { internal_format: gl::DEPTH24_STENCIL8, .. })));
+    }
+}
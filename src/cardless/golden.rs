@@ -0,0 +1,117 @@
+use std::{env, fs, path::PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use super::error::CardlessError;
+
+/// Compares rendered frames against reference images stored as PNG files.
+///
+/// When `CARDLESS_BLESS` is set in the environment, the frame is written as
+/// the new reference instead of being compared. A missing reference is an
+/// error otherwise, so a deleted or misnamed one cannot pass unnoticed.
+pub struct Golden {
+    pub reference_dir: PathBuf,
+    pub output_dir: PathBuf,
+    /// Largest difference allowed in any channel of a pixel.
+    pub tolerance: u8,
+}
+
+pub struct Mismatch {
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+    pub diff: RgbaImage,
+}
+
+impl Golden {
+    pub fn new<P, Q>(reference_dir: P, output_dir: Q) -> Self
+    where P: Into<PathBuf>, Q: Into<PathBuf> {
+        Self {
+            reference_dir: reference_dir.into(),
+            output_dir: output_dir.into(),
+            tolerance: 2,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Checks `actual` against `<reference_dir>/<name>.png`. On mismatch the
+    /// frame and a diff image are saved to `output_dir` for inspection.
+    pub fn check(&self, name: &str, actual: &RgbaImage) -> Result<(), CardlessError> {
+        let reference_path = self.reference_dir.join(format!("{}.png", name));
+
+        if env::var_os("CARDLESS_BLESS").is_some() {
+            fs::create_dir_all(&self.reference_dir)?;
+            return Ok(actual.save(&reference_path)?);
+        }
+        if !reference_path.exists() {
+            return Err(CardlessError::MissingGolden(reference_path));
+        }
+
+        let expected = image::open(&reference_path)?.to_rgba8();
+        let mismatch = match compare(actual, &expected, self.tolerance) {
+            Some(mismatch) => mismatch,
+            None => return Ok(()),
+        };
+
+        fs::create_dir_all(&self.output_dir)?;
+        let actual_path = self.output_dir.join(format!("{}.actual.png", name));
+        let diff_path = self.output_dir.join(format!("{}.diff.png", name));
+        actual.save(&actual_path)?;
+        mismatch.diff.save(&diff_path)?;
+
+        Err(CardlessError::GoldenMismatch {
+            name: name.to_string(),
+            differing_pixels: mismatch.differing_pixels,
+            max_difference: mismatch.max_difference,
+            tolerance: self.tolerance,
+            actual: actual_path,
+            diff: diff_path,
+        })
+    }
+}
+
+/// Returns `None` if every channel of every pixel is within `tolerance`.
+/// Otherwise the diff shows matching pixels as faded greyscale of `expected`
+/// and differing ones in red.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Option<Mismatch> {
+    if actual.dimensions() != expected.dimensions() {
+        let (width, height) = actual.dimensions();
+        return Some(Mismatch {
+            differing_pixels: (width * height) as usize,
+            max_difference: u8::MAX,
+            diff: RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255])),
+        });
+    }
+
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        let difference = actual_pixel.0.iter()
+            .zip(expected_pixel.0.iter())
+            .map(|(&a, &b)| (a as i16 - b as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap_or(0);
+
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            differing_pixels += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+            let faded = 128 + luma / 2;
+            diff.put_pixel(x, y, Rgba([faded, faded, faded, 255]));
+        }
+    }
+
+    match differing_pixels {
+        0 => None,
+        _ => Some(Mismatch { differing_pixels, max_difference, diff }),
+    }
+}
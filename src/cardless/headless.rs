@@ -0,0 +1,144 @@
+use std::{ffi::{CString, c_void}, os::raw::c_char, ptr::{null, null_mut}};
+
+use super::{device, error::CardlessError, framebuffer::Framebuffer};
+
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+const EGL_OPENGL_API: u32 = 0x30A2;
+const EGL_SURFACE_TYPE: i32 = 0x3033;
+const EGL_PBUFFER_BIT: i32 = 0x0001;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_OPENGL_BIT: i32 = 0x0008;
+const EGL_RED_SIZE: i32 = 0x3024;
+const EGL_GREEN_SIZE: i32 = 0x3023;
+const EGL_BLUE_SIZE: i32 = 0x3022;
+const EGL_ALPHA_SIZE: i32 = 0x3021;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: i32 = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: i32 = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: i32 = 0x0001;
+const EGL_NONE: i32 = 0x3038;
+const EGL_TRUE: u32 = 1;
+
+#[link(name = "EGL")]
+extern "C" {
+    fn eglGetPlatformDisplay(platform: u32, native_display: *mut c_void, attrib_list: *const isize) -> EGLDisplay;
+    fn eglInitialize(display: EGLDisplay, major: *mut i32, minor: *mut i32) -> u32;
+    fn eglBindAPI(api: u32) -> u32;
+    fn eglChooseConfig(display: EGLDisplay, attrib_list: *const i32, configs: *mut EGLConfig, config_size: i32, num_config: *mut i32) -> u32;
+    fn eglCreateContext(display: EGLDisplay, config: EGLConfig, share_context: EGLContext, attrib_list: *const i32) -> EGLContext;
+    fn eglMakeCurrent(display: EGLDisplay, draw: EGLSurface, read: EGLSurface, context: EGLContext) -> u32;
+    fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+    fn eglDestroyContext(display: EGLDisplay, context: EGLContext) -> u32;
+    fn eglTerminate(display: EGLDisplay) -> u32;
+    fn eglGetError() -> i32;
+}
+
+/// OpenGL 3.3 core context without a window, created through the EGL
+/// surfaceless platform. With Mesa this runs on llvmpipe when no GPU is present.
+///
+/// Everything is rendered into an offscreen framebuffer of fixed size,
+/// which stays bound as the draw target for the lifetime of the context.
+pub struct HeadlessContext {
+    display: EGLDisplay,
+    context: EGLContext,
+    /// Always set, only taken on drop to delete it while the context exists.
+    framebuffer: Option<Framebuffer>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl HeadlessContext {
+    pub fn try_new(width: u32, height: u32) -> Result<Self, CardlessError> {
+        let display = unsafe { eglGetPlatformDisplay(EGL_PLATFORM_SURFACELESS_MESA, null_mut(), null()) };
+        if display.is_null() {
+            return Err(CardlessError::Context(format!("eglGetPlatformDisplay failed: 0x{:x}", unsafe { eglGetError() })));
+        }
+
+        if unsafe { eglInitialize(display, null_mut(), null_mut()) } != EGL_TRUE {
+            return Err(CardlessError::Context(format!("eglInitialize failed: 0x{:x}", unsafe { eglGetError() })));
+        }
+
+        if unsafe { eglBindAPI(EGL_OPENGL_API) } != EGL_TRUE {
+            unsafe { eglTerminate(display); }
+            return Err(CardlessError::Context(format!("eglBindAPI failed: 0x{:x}", unsafe { eglGetError() })));
+        }
+
+        let config_attributes = [
+            EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+            EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT,
+            EGL_RED_SIZE, 8,
+            EGL_GREEN_SIZE, 8,
+            EGL_BLUE_SIZE, 8,
+            EGL_ALPHA_SIZE, 8,
+            EGL_NONE,
+        ];
+        let mut config = null_mut();
+        let mut configs_count = 0;
+        if unsafe { eglChooseConfig(display, config_attributes.as_ptr(), &mut config, 1, &mut configs_count) } != EGL_TRUE
+        || configs_count == 0 {
+            unsafe { eglTerminate(display); }
+            return Err(CardlessError::Context("no EGL config supports offscreen OpenGL rendering".to_string()));
+        }
+
+        let context_attributes = [
+            EGL_CONTEXT_MAJOR_VERSION, 3,
+            EGL_CONTEXT_MINOR_VERSION, 3,
+            EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            EGL_NONE,
+        ];
+        let context = unsafe { eglCreateContext(display, config, null_mut(), context_attributes.as_ptr()) };
+        if context.is_null() {
+            let error = unsafe { eglGetError() };
+            unsafe { eglTerminate(display); }
+            return Err(CardlessError::Context(format!("eglCreateContext failed: 0x{:x}", error)));
+        }
+
+        if unsafe { eglMakeCurrent(display, null_mut(), null_mut(), context) } != EGL_TRUE {
+            let error = unsafe { eglGetError() };
+            unsafe { eglDestroyContext(display, context); }
+            unsafe { eglTerminate(display); }
+            return Err(CardlessError::Context(format!("eglMakeCurrent failed: 0x{:x}", error)));
+        }
+
+        gl::load_with(|symbol| {
+            let symbol = CString::new(symbol).unwrap();
+            unsafe { eglGetProcAddress(symbol.as_ptr()) }
+        });
+
+        let mut headless = Self { display, context, framebuffer: None, width, height };
+
+        // Surfaceless contexts have no default framebuffer, so all drawing
+        // goes into this one instead.
+        let framebuffer = Framebuffer::try_new(width, height, true)?;
+        framebuffer.bind();
+        headless.framebuffer = Some(framebuffer);
+
+        Ok(headless)
+    }
+
+    /// Waits for all pending draws and copies the framebuffer into an image,
+    /// with the first row being the top of the frame.
+    pub fn read_frame(&self) -> image::RgbaImage {
+        device::with(|device| device.finish());
+        self.framebuffer().read_pixels()
+    }
+
+    /// Framebuffer everything is rendered into.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        self.framebuffer.as_ref().unwrap()
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        self.framebuffer.take();
+        unsafe { eglMakeCurrent(self.display, null_mut(), null_mut(), null_mut()); }
+        unsafe { eglDestroyContext(self.display, self.context); }
+        unsafe { eglTerminate(self.display); }
+    }
+}
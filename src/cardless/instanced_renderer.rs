@@ -0,0 +1,267 @@
+use std::mem::size_of;
+
+use super::{batch, buffer::{Buffer, BufferType, StreamingStrategy}, camera::{self, Camera2D}, device, error::CardlessError, shader::{Preprocessor, Shader, ShaderType}, shader_program::ShaderProgram, sprite::Sprite, texture::Texture, vertex_array::VertexArray, vertex_attribute::Vertex};
+
+/// Corner of the unit quad every sprite instance is drawn from.
+#[derive(Vertex)]
+#[repr(C)]
+pub struct QuadVertex {
+    pub corner: glm::Vec2,
+}
+
+/// Per-sprite data of [`InstancedRenderer`].
+#[derive(Vertex)]
+#[repr(C)]
+pub struct SpriteInstance {
+    /// Columns of the 2x2 matrix applied to the unit quad, scale and rotation.
+    #[vertex(location = 1)]
+    pub transform: glm::Vec4,
+    pub translation: glm::Vec2,
+    /// Offset and size of the sampled area, in UV space.
+    pub uv_rect: glm::Vec4,
+    pub color: [u8; 4],
+    /// Texture slot, filled in by [`InstancedRenderer::push_instance`].
+    pub texture: i32,
+}
+
+impl From<&Sprite> for SpriteInstance {
+    fn from(sprite: &Sprite) -> Self {
+        let (x, y) = sprite.transform();
+        let uv = sprite.uv_rect();
+
+        Self {
+            transform: glm::vec4(x.x, x.y, y.x, y.y),
+            translation: sprite.translation(),
+            uv_rect: glm::vec4(uv.position.x, uv.position.y, uv.size.x, uv.size.y),
+            color: sprite.color,
+            texture: 0,
+        }
+    }
+}
+
+impl SpriteInstance {
+    /// Untinted axis-aligned square covering the whole texture.
+    pub fn square(pos: glm::Vec2, size: glm::Vec2) -> Self {
+        Self {
+            transform: glm::vec4(size.x, 0., 0., size.y),
+            translation: pos,
+            uv_rect: glm::vec4(0., 0., 1., 1.),
+            color: [255; 4],
+            texture: 0,
+        }
+    }
+}
+
+/// Draws sprites as instances of one static quad, uploading a single
+/// [`SpriteInstance`] per sprite instead of 4 vertices and 6 indices.
+pub struct InstancedRenderer {
+    shader: ShaderProgram,
+    vertex_array: VertexArray,
+    _quad: Buffer<QuadVertex>,
+    _quad_elements: Buffer<u32>,
+
+    pub instances_capacity: usize,
+    instances: Buffer<SpriteInstance>,
+
+    pub textures_capacity: usize,
+    pub textures: Vec<u32>,
+
+    view_projection: glm::Mat4,
+}
+
+impl InstancedRenderer {
+    /// Shaders may include `cardless/textures.glsl` for `sample_texture`,
+    /// see `shaders/instanced.vert` for the attributes.
+    pub fn try_new(fragment: &str, vertex: &str) -> Result<Self, CardlessError> {
+        let textures_capacity = device::with(|device| device.max_texture_image_units()).min(16) as usize;
+
+        let corners = vec![
+            QuadVertex { corner: glm::vec2(0., 0.) },
+            QuadVertex { corner: glm::vec2(1., 0.) },
+            QuadVertex { corner: glm::vec2(0., 1.) },
+            QuadVertex { corner: glm::vec2(1., 1.) },
+        ];
+        let quad = Buffer::try_new(BufferType::VERTEX, corners, size_of::<QuadVertex>())?;
+        let quad_elements = Buffer::try_new(BufferType::ELEMENT, vec![0, 1, 2, 2, 1, 3], size_of::<u32>())?;
+        let instances = Buffer::try_new(BufferType::VERTEX, Vec::new(), size_of::<SpriteInstance>())?;
+        let vertex_array = VertexArray::try_new_instanced(&quad, &instances, &quad_elements)?;
+
+        let preprocessor = Preprocessor::new().with_texture_slots(textures_capacity);
+        let fragment = Shader::try_preprocessed(ShaderType::FRAGMENT, &preprocessor.preprocess(fragment)?)?;
+        let vertex = Shader::try_preprocessed(ShaderType::VERTEX, &preprocessor.preprocess(vertex)?)?;
+        let layout: Vec<_> = QuadVertex::get_attributes_layout().into_iter()
+            .chain(SpriteInstance::get_attributes_layout())
+            .collect();
+        let shader = ShaderProgram::try_new(vertex, fragment, &layout)?;
+
+        Ok(Self {
+            shader,
+            vertex_array,
+            _quad: quad,
+            _quad_elements: quad_elements,
+            instances_capacity: 16384,
+            instances,
+            textures_capacity,
+            textures: Vec::with_capacity(textures_capacity),
+            view_projection: camera::identity(),
+        })
+    }
+
+    pub fn bind(&mut self) {
+        self.shader.activate();
+        let slots: Vec<i32> = (0..self.textures_capacity as i32).collect();
+        self.shader.set_uniform("u_texture", &slots);
+        self.shader.set_uniform("u_view_projection", &self.view_projection);
+    }
+
+    /// See [`BatchRenderer::set_view_projection`](super::simple2d_renderer::BatchRenderer::set_view_projection).
+    pub fn set_view_projection(&mut self, view_projection: glm::Mat4) {
+        if !self.instances.data.is_empty() {
+            self.flush();
+        }
+        self.view_projection = view_projection;
+        self.shader.activate().set_uniform("u_view_projection", &view_projection);
+    }
+
+    pub fn set_camera(&mut self, camera: &Camera2D) {
+        self.set_view_projection(camera.view_projection());
+    }
+
+    /// See [`Buffer::set_streaming`]. Instances are always drawn from the
+    /// start of the buffer, so [`StreamingStrategy::Ring`] is rejected.
+    pub fn set_streaming(&mut self, strategy: StreamingStrategy) -> Result<(), CardlessError> {
+        if let StreamingStrategy::Ring { .. } = strategy {
+            return Err(CardlessError::InstanceStreaming(strategy));
+        }
+        self.instances.set_streaming(strategy, self.instances_capacity)
+    }
+
+    pub fn push_square_texture(&mut self, pos: glm::Vec2, size: glm::Vec2, texture: &Texture) {
+        self.push_instance(SpriteInstance::square(pos, size), texture);
+    }
+
+    pub fn draw_sprite(&mut self, sprite: &Sprite, texture: &Texture) {
+        self.push_instance(sprite.into(), texture);
+    }
+
+    pub fn push_instance(&mut self, mut instance: SpriteInstance, texture: &Texture) {
+        if self.instances.data.len() + 1 > self.instances_capacity {
+            self.flush();
+        }
+
+        instance.texture = match self.get_texture_slot(texture) {
+            Some(slot) => slot,
+            None => {
+                self.flush();
+                self.get_texture_slot(texture).unwrap()
+            }
+        };
+
+        self.instances.data.push(instance);
+    }
+
+    fn get_texture_slot(&mut self, texture: &Texture) -> Option<i32> {
+        batch::texture_slot(&mut self.textures, self.textures_capacity, texture.handler)
+    }
+
+    pub fn flush(&mut self) {
+        if self.instances.data.is_empty() {
+            return;
+        }
+
+        self.shader.activate();
+        self.vertex_array.bind();
+        self.instances.flush();
+
+        device::with(|device| {
+            for (slot, &texture) in self.textures.iter().enumerate() {
+                device.active_texture(slot as u32);
+                device.bind_texture(texture);
+            }
+
+            device.draw_elements_instanced(6, self.instances.data.len());
+        });
+
+        if self.textures.len() == self.textures_capacity {
+            self.textures.clear();
+        }
+        self.instances.data.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glm::vec2;
+
+    use crate::cardless::{buffer::StreamingStrategy, device::{self, Command}, error::CardlessError, texture::Texture};
+
+    use super::InstancedRenderer;
+
+    fn draws(log: &Rc<RefCell<Vec<Command>>>) -> Vec<usize> {
+        log.borrow().iter().filter_map(|command| match command {
+            Command::DrawElementsInstanced { count: 6, instances } => Some(*instances),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn instance_attributes_advance_per_instance() {
+        let log = device::record();
+        InstancedRenderer::try_new("", "").unwrap();
+
+        let divisors: Vec<u32> = log.borrow().iter().filter_map(|command| match command {
+            Command::VertexAttributeDivisor { index, divisor: 1 } => Some(*index),
+            _ => None,
+        }).collect();
+        assert_eq!(divisors, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn flush_draws_one_instance_per_sprite() {
+        let log = device::record();
+        let mut ir = InstancedRenderer::try_new("", "").unwrap();
+        let texture = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+
+        for i in 0..3 {
+            ir.push_square_texture(vec2(i as f32, 0.), vec2(1., 1.), &texture);
+        }
+        ir.flush();
+
+        assert_eq!(draws(&log), vec![3]);
+    }
+
+    #[test]
+    fn running_out_of_instances_flushes() {
+        let log = device::record();
+        let mut ir = InstancedRenderer::try_new("", "").unwrap();
+        let texture = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+
+        for _ in 0..ir.instances_capacity + 1 {
+            ir.push_square_texture(vec2(0., 0.), vec2(1., 1.), &texture);
+        }
+
+        assert_eq!(draws(&log), vec![ir.instances_capacity]);
+    }
+
+    #[test]
+    fn instances_cannot_stream_through_a_ring() {
+        device::record();
+        let mut ir = InstancedRenderer::try_new("", "").unwrap();
+
+        let ring = StreamingStrategy::Ring { regions: 3 };
+        assert!(matches!(ir.set_streaming(ring), Err(CardlessError::InstanceStreaming(strategy)) if strategy == ring));
+        assert!(ir.set_streaming(StreamingStrategy::SubData).is_ok());
+    }
+
+    #[test]
+    fn empty_flush_draws_nothing() {
+        let log = device::record();
+        let mut ir = InstancedRenderer::try_new("", "").unwrap();
+
+        log.borrow_mut().clear();
+        ir.flush();
+        assert!(log.borrow().is_empty());
+    }
+}
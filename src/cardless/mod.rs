@@ -1,9 +1,24 @@
-const LOG_MAX_LENGTH: usize = 512;
-
 pub mod buffer;
+pub mod device;
+pub mod diagnostic;
+pub mod error;
 pub mod shader;
 pub mod shader_program;
+pub mod uniform;
 pub mod vertex_attribute;
+pub mod vertex_array;
 pub mod texture;
+pub mod framebuffer;
 pub mod batch;
+pub mod sprite;
+pub mod camera;
+pub mod resolution;
+pub mod atlas;
+pub mod bundle;
 pub mod simple2d_renderer;
+pub mod instanced_renderer;
+pub mod post_process;
+#[cfg(feature = "headless")]
+pub mod golden;
+#[cfg(feature = "headless")]
+pub mod headless;
@@ -0,0 +1,295 @@
+//! Full-screen effects applied to a finished frame. The frame is drawn into
+//! the processor first, then every enabled effect runs in order, each reading
+//! the output of the one before:
+//!
+//! ```ignore
+//! let mut post = PostProcessor::try_new(width, height)?
+//!     .with_effect(PostEffect::try_bloom(0.7, 1.)?)
+//!     .with_effect(PostEffect::try_vignette(0.5, 0.6)?);
+//!
+//! post.capture(|| {
+//!     post.source().clear([0., 0., 0., 1.]);
+//!     br.draw_sprite(&player, &player_texture);
+//!     br.flush();
+//! });
+//! post.set_enabled("bloom", settings.bloom);
+//! post.apply();
+//! ```
+
+use std::mem::size_of;
+
+use image::RgbaImage;
+
+use super::{buffer::{Buffer, BufferType}, device, error::CardlessError, framebuffer::Framebuffer, shader::{Preprocessor, Shader, ShaderType}, shader_program::ShaderProgram, texture::Texture, uniform::Uniform, vertex_array::VertexArray, vertex_attribute::Vertex};
+
+const SCREEN_VERTEX_SHADER: &str = include_str!("../../shaders/post/screen.vert");
+/// Declarations shared by effect shaders, includable as `cardless/post.glsl`.
+pub const POST_GLSL: &str = include_str!("../../shaders/post/post.glsl");
+const COPY_SHADER: &str = include_str!("../../shaders/post/copy.frag");
+const BLOOM_SHADER: &str = include_str!("../../shaders/post/bloom.frag");
+const VIGNETTE_SHADER: &str = include_str!("../../shaders/post/vignette.frag");
+const COLOR_GRADE_SHADER: &str = include_str!("../../shaders/post/color_grade.frag");
+const CRT_SHADER: &str = include_str!("../../shaders/post/crt.frag");
+
+/// Corner of the quad covering the whole target, in clip space.
+#[derive(Vertex)]
+#[repr(C)]
+pub struct ScreenVertex {
+    pub position: glm::Vec2,
+}
+
+/// One full-screen pass. Its fragment shader reads the frame from `u_frame`
+/// and writes an opaque colour, see `shaders/post/post.glsl`.
+pub struct PostEffect {
+    /// Name to find the effect by in its [`PostProcessor`].
+    pub name: String,
+    pub enabled: bool,
+    program: ShaderProgram,
+    /// Extra textures by sampler name, bound from unit 1 on.
+    textures: Vec<(String, Texture)>,
+}
+
+impl PostEffect {
+    /// Effect running `fragment`, which may include `cardless/post.glsl` for
+    /// `frag_uv`, `u_frame`, `u_resolution` and the `finale_color` output.
+    pub fn try_new(name: &str, fragment: &str) -> Result<Self, CardlessError> {
+        let preprocessor = Preprocessor::new().with_file("cardless/post.glsl", POST_GLSL.to_string());
+        let vertex = Shader::try_preprocessed(ShaderType::VERTEX, &preprocessor.preprocess(SCREEN_VERTEX_SHADER)?)?;
+        let fragment = Shader::try_preprocessed(ShaderType::FRAGMENT, &preprocessor.preprocess(fragment)?)?;
+        let program = ShaderProgram::try_new(vertex, fragment, &ScreenVertex::get_attributes_layout())?;
+
+        Ok(Self { name: name.to_string(), enabled: true, program, textures: Vec::new() })
+    }
+
+    /// Glow around pixels brighter than `threshold`, named `bloom`.
+    pub fn try_bloom(threshold: f32, intensity: f32) -> Result<Self, CardlessError> {
+        let mut effect = Self::try_new("bloom", BLOOM_SHADER)?;
+        effect.set_uniform("u_threshold", &threshold);
+        effect.set_uniform("u_intensity", &intensity);
+        effect.set_uniform("u_radius", &8f32);
+        Ok(effect)
+    }
+
+    /// Darkens the edges from `radius`, 0 being the centre and 1 the corners,
+    /// named `vignette`.
+    pub fn try_vignette(radius: f32, strength: f32) -> Result<Self, CardlessError> {
+        let mut effect = Self::try_new("vignette", VIGNETTE_SHADER)?;
+        effect.set_uniform("u_radius", &radius);
+        effect.set_uniform("u_strength", &strength);
+        Ok(effect)
+    }
+
+    /// Maps colours through `lut`, laid out like [`identity_lut`], named
+    /// `color_grade`. The LUT should be linearly filtered, clamped and
+    /// without mipmaps.
+    pub fn try_color_grade(lut: Texture, intensity: f32) -> Result<Self, CardlessError> {
+        let mut effect = Self::try_new("color_grade", COLOR_GRADE_SHADER)?.with_texture("u_lut", lut);
+        effect.set_uniform("u_intensity", &intensity);
+        Ok(effect)
+    }
+
+    /// Scanlines every `line_height` pixels and a faint RGB mask, named `crt`.
+    pub fn try_crt(line_height: f32, strength: f32) -> Result<Self, CardlessError> {
+        let mut effect = Self::try_new("crt", CRT_SHADER)?;
+        effect.set_uniform("u_line_height", &line_height);
+        effect.set_uniform("u_strength", &strength);
+        Ok(effect)
+    }
+
+    /// Makes `texture` readable from the `sampler` uniform.
+    pub fn with_texture(mut self, sampler: &str, texture: Texture) -> Self {
+        let unit = self.textures.len() as i32 + 1;
+        self.set_uniform(sampler, &unit);
+        self.textures.push((sampler.to_string(), texture));
+        self
+    }
+
+    /// Sets a uniform of the effect's program, kept for every later frame.
+    pub fn set_uniform<T: Uniform + ?Sized>(&mut self, name: &str, value: &T) {
+        self.program.activate().set_uniform(name, value);
+    }
+
+    /// Draws the screen quad of the bound vertex array, reading `frame`.
+    fn draw(&mut self, frame: &Texture, resolution: glm::Vec2) {
+        self.program.activate().set_uniform("u_frame", &0);
+        // Optimised out by effects not using it.
+        if self.program.active_uniforms.iter().any(|uniform| uniform.name == "u_resolution") {
+            self.program.set_uniform("u_resolution", &resolution);
+        }
+
+        device::with(|device| {
+            device.active_texture(0);
+            device.bind_texture(frame.handler);
+            for (unit, (_, texture)) in self.textures.iter().enumerate() {
+                device.active_texture(unit as u32 + 1);
+                device.bind_texture(texture.handler);
+            }
+
+            device.draw_elements(6, 0, 0);
+        });
+    }
+}
+
+/// Captures a frame into an offscreen target and runs a chain of
+/// [`PostEffect`]s on it.
+pub struct PostProcessor {
+    pub effects: Vec<PostEffect>,
+    /// Ping-ponged between passes, the first one capturing the frame.
+    targets: [Framebuffer; 2],
+    /// Used when every effect is disabled.
+    copy: PostEffect,
+    vertex_array: VertexArray,
+    _vertices: Buffer<ScreenVertex>,
+    _elements: Buffer<u32>,
+}
+
+impl PostProcessor {
+    pub fn try_new(width: u32, height: u32) -> Result<Self, CardlessError> {
+        let corners = vec![
+            ScreenVertex { position: glm::vec2(-1., -1.) },
+            ScreenVertex { position: glm::vec2(1., -1.) },
+            ScreenVertex { position: glm::vec2(-1., 1.) },
+            ScreenVertex { position: glm::vec2(1., 1.) },
+        ];
+        let vertices = Buffer::try_new(BufferType::VERTEX, corners, size_of::<ScreenVertex>())?;
+        let elements = Buffer::try_new(BufferType::ELEMENT, vec![0, 1, 2, 2, 1, 3], size_of::<u32>())?;
+        let vertex_array = VertexArray::try_new(&vertices, &elements)?;
+
+        Ok(Self {
+            effects: Vec::new(),
+            targets: [Framebuffer::try_new(width, height, true)?, Framebuffer::try_new(width, height, false)?],
+            copy: PostEffect::try_new("copy", COPY_SHADER)?,
+            vertex_array,
+            _vertices: vertices,
+            _elements: elements,
+        })
+    }
+
+    /// Appends `effect` to the end of the chain.
+    pub fn with_effect(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut PostEffect> {
+        self.effects.iter_mut().find(|effect| effect.name == name)
+    }
+
+    /// Turns the effect `name` on or off, returning whether it exists.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.effect_mut(name) {
+            Some(effect) => {
+                effect.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recreates the targets, e.g. after the window was resized.
+    pub fn try_resize(&mut self, width: u32, height: u32) -> Result<(), CardlessError> {
+        self.targets = [Framebuffer::try_new(width, height, true)?, Framebuffer::try_new(width, height, false)?];
+        Ok(())
+    }
+
+    /// Framebuffer frames are captured into, with depth and stencil.
+    pub fn source(&self) -> &Framebuffer {
+        &self.targets[0]
+    }
+
+    /// Runs `draw` with the source framebuffer bound, see [`Framebuffer::render`].
+    pub fn capture<R, F: FnOnce() -> R>(&self, draw: F) -> R {
+        self.targets[0].render(draw)
+    }
+
+    /// Runs the enabled effects on the captured frame, the last one drawing
+    /// into the framebuffer and viewport bound when called.
+    pub fn apply(&mut self) {
+        let (output, viewport) = device::with(|device| (device.bound_framebuffer(), device.get_viewport()));
+        self.vertex_array.bind();
+
+        let mut passes: Vec<&mut PostEffect> = self.effects.iter_mut().filter(|effect| effect.enabled).collect();
+        if passes.is_empty() {
+            passes.push(&mut self.copy);
+        }
+
+        let last = passes.len() - 1;
+        let mut input = 0;
+        for (index, effect) in passes.into_iter().enumerate() {
+            let frame = &self.targets[input].color;
+            if index == last {
+                device::with(|device| {
+                    device.bind_framebuffer(output);
+                    device.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                });
+                effect.draw(frame, glm::vec2(viewport[2] as f32, viewport[3] as f32));
+            } else {
+                let target = &self.targets[1 - input];
+                target.bind();
+                effect.draw(frame, glm::vec2(target.width as f32, target.height as f32));
+                input = 1 - input;
+            }
+        }
+    }
+}
+
+/// LUT leaving colours unchanged, to edit into a grade: `size` slices of
+/// `size` x `size` pixels side by side, red growing to the right within a
+/// slice, green downwards and blue from slice to slice. `size` must be at
+/// least 2 to span a channel.
+pub fn identity_lut(size: u32) -> Result<RgbaImage, CardlessError> {
+    let width = size.checked_mul(size).filter(|_| size >= 2).ok_or(CardlessError::InvalidLutSize(size))?;
+    let level = |value: u32| (value * 255 / (size - 1)) as u8;
+    Ok(RgbaImage::from_fn(width, size, |x, y| image::Rgba([level(x % size), level(y), level(x / size), 255])))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cardless::{device::{self, Command}, error::CardlessError};
+
+    use super::{identity_lut, PostEffect, PostProcessor};
+
+    #[test]
+    fn runs_enabled_effects_into_the_bound_target() {
+        let log = device::record();
+        device::with(|device| device.viewport(0, 0, 800, 600));
+
+        let mut post = PostProcessor::try_new(400, 300).unwrap()
+            .with_effect(PostEffect::try_new("a", "").unwrap())
+            .with_effect(PostEffect::try_new("b", "").unwrap())
+            .with_effect(PostEffect::try_new("c", "").unwrap());
+        assert!(post.set_enabled("b", false));
+        assert!(!post.set_enabled("missing", false));
+        let programs = (post.effects[0].program.handler, post.effects[2].program.handler);
+
+        log.borrow_mut().clear();
+        post.apply();
+
+        let log = log.borrow();
+        let bound: Vec<_> = log.iter().filter_map(|command| match command {
+            Command::BindFramebuffer(framebuffer) => Some(*framebuffer),
+            _ => None,
+        }).collect();
+        assert_eq!(bound, vec![post.targets[1].handler, 0]);
+        assert!(log.contains(&Command::UseProgram(programs.0)));
+        assert!(log.contains(&Command::UseProgram(programs.1)));
+        assert_eq!(log.iter().filter(|command| matches!(command, Command::DrawElements { .. })).count(), 2);
+        assert_eq!(log.last(), Some(&Command::DrawElements { count: 6, first: 0, base_vertex: 0 }));
+        assert!(log.contains(&Command::Viewport { x: 0, y: 0, width: 800, height: 600 }));
+    }
+
+    #[test]
+    fn identity_lut_spans_every_channel() {
+        let lut = identity_lut(4).unwrap();
+        assert_eq!(lut.dimensions(), (16, 4));
+        assert_eq!(lut.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(lut.get_pixel(3, 1).0, [255, 85, 0, 255]);
+        assert_eq!(lut.get_pixel(15, 3).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn identity_lut_rejects_unusable_sizes() {
+        assert!(matches!(identity_lut(1), Err(CardlessError::InvalidLutSize(1))));
+        assert!(matches!(identity_lut(1 << 16), Err(CardlessError::InvalidLutSize(65536))));
+    }
+}
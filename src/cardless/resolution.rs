@@ -0,0 +1,217 @@
+//! Renders at a fixed logical resolution and scales the frame to whatever
+//! size the window has:
+//!
+//! ```ignore
+//! let screen = VirtualScreen::try_new(VirtualResolution::new(320, 180, Scaling::PixelPerfect))?;
+//! br.set_camera(&screen.resolution.camera());
+//!
+//! screen.render(|| {
+//!     screen.target().clear([0., 0., 0., 1.]);
+//!     br.draw_sprite(&player, &player_texture);
+//!     br.flush();
+//! });
+//! screen.present(window_width, window_height);
+//!
+//! let cursor = screen.resolution.to_logical(window_size, cursor_position);
+//! ```
+
+use super::{camera::Camera2D, device, error::CardlessError, framebuffer::Framebuffer, sprite::Rect, texture::Filter};
+
+/// How the logical frame is fitted into a window of another size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// Covers the window, distorting the frame if the aspect ratios differ.
+    Stretch,
+    /// As large as fits whole, bars filling the rest of the window.
+    Letterbox,
+    /// Covers the window, cutting off what overflows.
+    Crop,
+    /// Like [`Scaling::Letterbox`], but only by whole multiples so every
+    /// logical pixel is the same size.
+    PixelPerfect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualResolution {
+    pub width: u32,
+    pub height: u32,
+    pub scaling: Scaling,
+}
+
+impl VirtualResolution {
+    pub fn new(width: u32, height: u32, scaling: Scaling) -> Self {
+        Self { width, height, scaling }
+    }
+
+    fn size(&self) -> glm::Vec2 {
+        glm::vec2(self.width as f32, self.height as f32)
+    }
+
+    /// Area of a `window` sized window the frame is drawn to, in pixels from
+    /// its top-left corner. Cropped frames extend past the window.
+    pub fn viewport(&self, window: glm::Vec2) -> Rect {
+        let size = self.size();
+        let fit = (window.x / size.x).min(window.y / size.y);
+        let scale = match self.scaling {
+            Scaling::Stretch => return Rect::new(glm::vec2(0., 0.), window),
+            Scaling::Letterbox => fit,
+            Scaling::Crop => (window.x / size.x).max(window.y / size.y),
+            Scaling::PixelPerfect => fit.floor().max(1.),
+        };
+
+        let scaled = size * scale;
+        let mut position = (window - scaled) / 2.;
+        if self.scaling == Scaling::PixelPerfect {
+            position = glm::vec2(position.x.floor(), position.y.floor());
+        }
+        Rect::new(position, scaled)
+    }
+
+    /// Logical position under the window position `position`, e.g. the
+    /// cursor. Logical coordinates go from the bottom-left corner of the
+    /// frame to `(width, height)` at the top-right, as the world of
+    /// [`Self::camera`] does.
+    pub fn to_logical(&self, window: glm::Vec2, position: glm::Vec2) -> glm::Vec2 {
+        let viewport = self.viewport(window);
+        let relative = (position - viewport.position) / viewport.size;
+        glm::vec2(relative.x * self.width as f32, (1. - relative.y) * self.height as f32)
+    }
+
+    /// Window position of the logical position `logical`.
+    pub fn to_window(&self, window: glm::Vec2, logical: glm::Vec2) -> glm::Vec2 {
+        let viewport = self.viewport(window);
+        let relative = glm::vec2(logical.x / self.width as f32, 1. - logical.y / self.height as f32);
+        viewport.position + relative * viewport.size
+    }
+
+    /// Camera drawing logical coordinates one to one onto the frame.
+    pub fn camera(&self) -> Camera2D {
+        Camera2D::new(self.size()).with_position(self.size() / 2.)
+    }
+}
+
+/// Offscreen frame of a [`VirtualResolution`], scaled onto the window by
+/// [`Self::present`].
+pub struct VirtualScreen {
+    pub resolution: VirtualResolution,
+    /// Filter used when scaling by other than whole multiples, linear by
+    /// default.
+    pub filter: Filter,
+    /// Colour of the window around the frame.
+    pub bar_color: [f32; 4],
+    target: Framebuffer,
+}
+
+impl VirtualScreen {
+    pub fn try_new(resolution: VirtualResolution) -> Result<Self, CardlessError> {
+        Ok(Self {
+            resolution,
+            filter: Filter::Linear,
+            bar_color: [0., 0., 0., 1.],
+            target: Framebuffer::try_new(resolution.width, resolution.height, true)?,
+        })
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_bar_color(mut self, bar_color: [f32; 4]) -> Self {
+        self.bar_color = bar_color;
+        self
+    }
+
+    /// Framebuffer of the logical resolution frames are drawn into.
+    pub fn target(&self) -> &Framebuffer {
+        &self.target
+    }
+
+    /// Runs `draw` with the logical frame as target, see [`Framebuffer::render`].
+    pub fn render<R, F: FnOnce() -> R>(&self, draw: F) -> R {
+        self.target.render(draw)
+    }
+
+    /// Clears the bound framebuffer, a window of `width` x `height` pixels,
+    /// to the bar colour and scales the frame onto it.
+    pub fn present(&self, width: u32, height: u32) {
+        let viewport = self.resolution.viewport(glm::vec2(width as f32, height as f32));
+        let left = viewport.position.x.round() as i32;
+        let right = (viewport.position.x + viewport.size.x).round() as i32;
+        let bottom = (height as f32 - viewport.position.y - viewport.size.y).round() as i32;
+        let top = (height as f32 - viewport.position.y).round() as i32;
+
+        let filter = match (self.resolution.scaling, self.filter) {
+            (Scaling::PixelPerfect, _) | (_, Filter::Nearest) => gl::NEAREST,
+            (_, Filter::Linear) => gl::LINEAR,
+        };
+
+        device::with(|device| {
+            let window = device.bound_framebuffer();
+            device.clear(self.bar_color);
+            device.blit_framebuffer(
+                self.target.handler,
+                [0, 0, self.target.width as i32, self.target.height as i32],
+                window,
+                [left, bottom, right, top],
+                filter,
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::vec2;
+
+    use crate::cardless::device::{self, Command};
+
+    use super::{Scaling, VirtualResolution, VirtualScreen};
+
+    #[test]
+    fn policies_place_the_frame() {
+        let window = vec2(1000., 500.);
+        let viewport = |scaling| {
+            let rect = VirtualResolution::new(320, 200, scaling).viewport(window);
+            (rect.position, rect.size)
+        };
+
+        assert_eq!(viewport(Scaling::Stretch), (vec2(0., 0.), vec2(1000., 500.)));
+        assert_eq!(viewport(Scaling::Letterbox), (vec2(100., 0.), vec2(800., 500.)));
+        assert_eq!(viewport(Scaling::Crop), (vec2(0., -62.5), vec2(1000., 625.)));
+        assert_eq!(viewport(Scaling::PixelPerfect), (vec2(180., 50.), vec2(640., 400.)));
+    }
+
+    #[test]
+    fn input_maps_back_to_logical_pixels() {
+        let resolution = VirtualResolution::new(320, 200, Scaling::PixelPerfect);
+        let window = vec2(1000., 500.);
+
+        assert_eq!(resolution.to_logical(window, vec2(180., 450.)), vec2(0., 0.));
+        assert_eq!(resolution.to_logical(window, vec2(500., 250.)), vec2(160., 100.));
+        assert_eq!(resolution.to_window(window, vec2(320., 200.)), vec2(820., 50.));
+
+        // Same place the camera draws logical coordinates to.
+        let camera = resolution.camera();
+        assert_eq!(camera.world_to_screen(vec2(80., 150.)), vec2(80., 50.));
+    }
+
+    #[test]
+    fn present_blits_into_the_bound_framebuffer() {
+        let log = device::record();
+
+        let screen = VirtualScreen::try_new(VirtualResolution::new(320, 200, Scaling::Letterbox)).unwrap();
+        screen.present(1000, 500);
+
+        assert_eq!(log.borrow()[log.borrow().len() - 2..], [
+            Command::Clear([0., 0., 0., 1.]),
+            Command::BlitFramebuffer {
+                source: screen.target().handler,
+                source_rect: [0, 0, 320, 200],
+                destination: 0,
+                destination_rect: [100, 0, 900, 500],
+                filter: gl::LINEAR,
+            },
+        ]);
+    }
+}
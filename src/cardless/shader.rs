@@ -1,46 +1,345 @@
-use std::{ffi::CString, ptr::{null, null_mut}};
+use std::{collections::HashMap, fmt, fs, path::{Path, PathBuf}};
 
-use super::LOG_MAX_LENGTH;
+use super::{device, diagnostic, error::CardlessError};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ShaderType {
     VERTEX,
     FRAGMENT,
 }
 
+impl fmt::Display for ShaderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderType::VERTEX => write!(f, "vertex"),
+            ShaderType::FRAGMENT => write!(f, "fragment"),
+        }
+    }
+}
+
 pub struct Shader {
     pub handler: u32,
 }
 
 impl Shader {
-    pub fn try_new(shader_type: ShaderType, source: &str) -> Result<Self, String> {
-        let handler = match shader_type {
-            ShaderType::VERTEX => unsafe { gl::CreateShader(gl::VERTEX_SHADER) },
-            ShaderType::FRAGMENT => unsafe { gl::CreateShader(gl::FRAGMENT_SHADER) },
+    pub fn try_new(stage: ShaderType, source: &str) -> Result<Self, CardlessError> {
+        let shader_type = match stage {
+            ShaderType::VERTEX => gl::VERTEX_SHADER,
+            ShaderType::FRAGMENT => gl::FRAGMENT_SHADER,
         };
+        // OpenGL takes the source as a C string.
+        if let Some(offset) = source.find('\0') {
+            let log = format!("source contains a NUL byte at offset {}", offset);
+            return Err(CardlessError::Compile { stage, log, diagnostics: Vec::new() });
+        }
 
-        let source = CString::new(source.as_bytes()).unwrap();
-        unsafe { gl::ShaderSource(handler, 1, &source.as_ptr(), null()); }
-        unsafe { gl::CompileShader(handler); }
+        let (handler, log) = device::with(|device| {
+            let handler = device.create_shader(shader_type);
+            match device.compile_shader(handler, source) {
+                true => (handler, None),
+                false => (handler, Some(device.shader_info_log(handler))),
+            }
+        });
 
-        let mut success = gl::TRUE as i32;
-        unsafe { gl::GetShaderiv(handler, gl::COMPILE_STATUS, &mut success); }
-        if success != gl::TRUE as i32 {
-            let mut log: Vec<u8> = Vec::with_capacity(LOG_MAX_LENGTH);
-            unsafe { gl::GetShaderInfoLog(handler, LOG_MAX_LENGTH as i32, null_mut(), log.as_mut_ptr() as *mut i8); }
+        let shader = Self { handler };
+        match log {
+            Some(log) => {
+                let diagnostics = diagnostic::parse_log(stage, &log, source);
+                Err(CardlessError::Compile { stage, log, diagnostics })
+            }
+            None => Ok(shader),
+        }
+    }
+}
 
-            let log = match std::str::from_utf8(&log) {
-                Ok(s) => s,
-                Err(e) => std::str::from_utf8(&log[..e.valid_up_to()]).unwrap()
-            };
+impl Shader {
+    /// Compiles preprocessed source, with diagnostics pointing back into the
+    /// file and line each statement came from.
+    pub fn try_preprocessed(stage: ShaderType, source: &Preprocessed) -> Result<Self, CardlessError> {
+        Self::try_new(stage, &source.source).map_err(|e| match e {
+            CardlessError::Compile { stage, log, mut diagnostics } => {
+                for diagnostic in &mut diagnostics {
+                    if let Some((file, line)) = diagnostic.line.and_then(|line| source.locate(line)) {
+                        diagnostic.file = file.map(Path::to_path_buf);
+                        diagnostic.line = Some(line);
+                    }
+                }
+                CardlessError::Compile { stage, log, diagnostics }
+            }
+            e => e,
+        })
+    }
 
-            Err(log.to_string())
-        } else { Ok(Self { handler }) }
+    pub fn try_from_file<P>(stage: ShaderType, path: P) -> Result<Self, CardlessError>
+    where P: AsRef<Path> {
+        Self::try_preprocessed(stage, &Preprocessor::new().preprocess_file(path)?)
     }
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        unsafe { gl::DeleteShader(self.handler); }
+        device::with(|device| device.delete_shader(self.handler));
     }
 }
 
+/// Expands `#include "name"` lines and injects `#define`s right after the
+/// `#version` line. Includes are looked up in the files registered with
+/// [`Self::with_file`] first, then next to the including file, then in
+/// [`Self::with_directory`].
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    files: HashMap<String, String>,
+    directory: Option<PathBuf>,
+    defines: Vec<(String, String)>,
+}
+
+/// Output of [`Preprocessor`], remembering where each of its lines came from.
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Files read from disk, the top-level one included.
+    pub dependencies: Vec<PathBuf>,
+    /// Path or name of every file taking part, the top-level one first.
+    files: Vec<Option<PathBuf>>,
+    /// Index in `files` and 1-based line there of each output line,
+    /// `None` for injected lines.
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl Preprocessed {
+    /// File and line that the 1-based `line` of the output was copied from.
+    pub fn locate(&self, line: usize) -> Option<(Option<&Path>, usize)> {
+        let (file, line) = (*self.lines.get(line.checked_sub(1)?)?)?;
+        Some((self.files[file].as_deref(), line))
+    }
+
+    fn push(&mut self, line: &str, origin: Option<(usize, usize)>) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.lines.push(origin);
+    }
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `source` includable as `name` without touching the disk.
+    pub fn with_file(mut self, name: &str, source: String) -> Self {
+        self.files.insert(name.to_string(), source);
+        self
+    }
+
+    pub fn with_directory<P: AsRef<Path>>(mut self, directory: P) -> Self {
+        self.directory = Some(directory.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn define<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Defines `MAX_TEXTURE_SLOTS` and provides `cardless/textures.glsl`,
+    /// see [`texture_slots_glsl`].
+    pub fn with_texture_slots(self, slots: usize) -> Self {
+        self.define("MAX_TEXTURE_SLOTS", slots)
+            .with_file("cardless/textures.glsl", texture_slots_glsl(slots))
+    }
+
+    pub fn preprocess(&self, source: &str) -> Result<Preprocessed, CardlessError> {
+        self.run(source, None)
+    }
+
+    pub fn preprocess_file<P: AsRef<Path>>(&self, path: P) -> Result<Preprocessed, CardlessError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|error| CardlessError::File { path: path.to_path_buf(), error })?;
+
+        self.run(&source, Some(path))
+    }
+
+    fn run(&self, source: &str, path: Option<&Path>) -> Result<Preprocessed, CardlessError> {
+        let mut output = Preprocessed {
+            source: String::new(),
+            dependencies: path.map(Path::to_path_buf).into_iter().collect(),
+            files: vec![path.map(Path::to_path_buf)],
+            lines: Vec::new(),
+        };
+
+        // `#version` has to stay the first statement, so defines go after it.
+        let has_version = source.lines().any(is_version);
+        if !has_version {
+            self.push_defines(&mut output);
+        }
+
+        let mut stack = Vec::new();
+        self.expand(source, 0, &mut stack, &mut output)?;
+
+        Ok(output)
+    }
+
+    fn push_defines(&self, output: &mut Preprocessed) {
+        for (name, value) in &self.defines {
+            output.push(&format!("#define {} {}", name, value), None);
+        }
+    }
+
+    fn expand(&self, source: &str, file: usize, stack: &mut Vec<String>, output: &mut Preprocessed) -> Result<(), CardlessError> {
+        for (i, line) in source.lines().enumerate() {
+            let error = |message: String| CardlessError::Preprocess {
+                file: output.files[file].clone(),
+                line: i + 1,
+                message,
+            };
+
+            let name = match parse_include(line) {
+                None => {
+                    output.push(line, Some((file, i + 1)));
+                    if file == 0 && is_version(line) {
+                        self.push_defines(output);
+                    }
+                    continue;
+                }
+                Some(Err(message)) => return Err(error(message.to_string())),
+                Some(Ok(name)) => name,
+            };
+
+            if stack.iter().any(|included| included == name) {
+                return Err(error(format!("\"{}\" includes itself", name)));
+            }
+
+            let directory = output.files[file].as_deref().and_then(Path::parent);
+            let (path, included) = match self.resolve(name, directory) {
+                Some(resolved) => resolved,
+                None => return Err(error(format!("cannot find include \"{}\"", name))),
+            };
+
+            if self.files.contains_key(name) {
+                output.files.push(Some(PathBuf::from(name)));
+            } else {
+                output.dependencies.push(path.clone());
+                output.files.push(Some(path));
+            }
+
+            stack.push(name.to_string());
+            self.expand(&included, output.files.len() - 1, stack, output)?;
+            stack.pop();
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, name: &str, directory: Option<&Path>) -> Option<(PathBuf, String)> {
+        if let Some(source) = self.files.get(name) {
+            return Some((PathBuf::from(name), source.clone()));
+        }
+
+        directory.into_iter()
+            .chain(self.directory.as_deref())
+            .map(|directory| directory.join(name))
+            .find_map(|path| fs::read_to_string(&path).ok().map(|source| (path, source)))
+    }
+}
+
+fn is_version(line: &str) -> bool {
+    line.trim_start().starts_with("#version")
+}
+
+// #include "name"
+fn parse_include(line: &str) -> Option<Result<&str, &'static str>> {
+    let directive = line.trim().strip_prefix('#')?.trim_start().strip_prefix("include")?;
+
+    let name = directive.trim()
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+        .filter(|name| !name.is_empty());
+    Some(name.ok_or("expected #include \"file\""))
+}
+
+/// GLSL declaring `u_texture[slots]` and `vec4 sample_texture(int slot, vec2 uv)`.
+/// GLSL 3.30 only indexes sampler arrays with constants, hence the switch.
+pub fn texture_slots_glsl(slots: usize) -> String {
+    let mut glsl = format!("uniform sampler2D u_texture[{}];\n\n", slots);
+    glsl.push_str("vec4 sample_texture(int slot, vec2 uv) {\n    switch(slot) {\n");
+    for slot in 0..slots {
+        glsl.push_str(&format!("        case {0}: return texture(u_texture[{0}], uv);\n", slot));
+    }
+    glsl.push_str("    }\n    return vec4(0.4, 0.6, 0.2, 1);\n}\n");
+    glsl
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::cardless::{device, error::CardlessError};
+
+    use super::{Preprocessor, Shader, ShaderType};
+
+    #[test]
+    fn injects_defines_after_version() {
+        let output = Preprocessor::new()
+            .define("MAX_TEXTURE_SLOTS", 8)
+            .preprocess("#version 330 core\nvoid main() {}\n")
+            .unwrap();
+
+        assert_eq!(output.source, "#version 330 core\n#define MAX_TEXTURE_SLOTS 8\nvoid main() {}\n");
+        assert_eq!(output.locate(2), None);
+        assert_eq!(output.locate(3), Some((None, 2)));
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let output = Preprocessor::new()
+            .with_file("a.glsl", "#include \"b.glsl\"\nfloat a;".to_string())
+            .with_file("b.glsl", "float b;".to_string())
+            .preprocess("#version 330 core\n  #  include \"a.glsl\"\nvoid main() {}")
+            .unwrap();
+
+        assert_eq!(output.source, "#version 330 core\nfloat b;\nfloat a;\nvoid main() {}\n");
+        assert_eq!(output.locate(2), Some((Some(Path::new("b.glsl")), 1)));
+        assert_eq!(output.locate(3), Some((Some(Path::new("a.glsl")), 2)));
+        assert_eq!(output.locate(4), Some((None, 3)));
+    }
+
+    #[test]
+    fn rejects_recursive_includes() {
+        let result = Preprocessor::new()
+            .with_file("a.glsl", "#include \"a.glsl\"".to_string())
+            .preprocess("#include \"a.glsl\"");
+
+        match result {
+            Err(CardlessError::Preprocess { file, line, .. }) => {
+                assert_eq!(file.as_deref(), Some(Path::new("a.glsl")));
+                assert_eq!(line, 1);
+            }
+            _ => panic!("expected a preprocess error"),
+        }
+    }
+
+    #[test]
+    fn generates_one_case_per_slot() {
+        let output = Preprocessor::new()
+            .with_texture_slots(4)
+            .preprocess("#version 330 core\n#include \"cardless/textures.glsl\"")
+            .unwrap();
+
+        assert!(output.source.contains("#define MAX_TEXTURE_SLOTS 4"));
+        assert!(output.source.contains("uniform sampler2D u_texture[4];"));
+        assert!(output.source.contains("case 3:"));
+        assert!(!output.source.contains("case 4:"));
+    }
+
+    #[test]
+    fn nul_bytes_fail_to_compile() {
+        let log = device::record();
+
+        match Shader::try_new(ShaderType::FRAGMENT, "void main() {}\0") {
+            Err(CardlessError::Compile { log, .. }) => assert_eq!(log, "source contains a NUL byte at offset 14"),
+            _ => panic!("expected a compile error"),
+        }
+        assert!(log.borrow().is_empty());
+    }
+}
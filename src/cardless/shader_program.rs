@@ -1,98 +1,139 @@
-use std::{collections::HashMap, ffi::{CString, c_void}, ptr::null_mut};
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, time::SystemTime};
 
-use super::{LOG_MAX_LENGTH, shader::Shader, vertex_attribute::{VertexAttribute, VertexAttributeType}};
+use super::{device, error::CardlessError, shader::{Preprocessor, Shader, ShaderType}, uniform::{self, ActiveUniform, Uniform}, vertex_attribute::{self, ActiveAttribute, VertexAttribute}};
 
 pub struct ShaderProgram {
     pub handler: u32,
     pub uniforms: HashMap<String, i32>,
+    pub active_uniforms: Vec<ActiveUniform>,
+    pub active_attributes: Vec<ActiveAttribute>,
+    files: Option<ShaderFiles>,
+    debug: bool,
+    warned: HashSet<String>,
+}
+
+/// Sources of a program built with [`ShaderProgram::try_from_files`],
+/// along with the files they include and their modification times at the
+/// last (re)load.
+struct ShaderFiles {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    preprocessor: Preprocessor,
+    dependencies: Vec<PathBuf>,
+    modified: Option<Vec<SystemTime>>,
+}
+
+impl ShaderFiles {
+    fn poll(&self) -> Option<Vec<SystemTime>> {
+        self.dependencies.iter()
+            .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+
+    fn try_link(&mut self, layout: &[VertexAttribute]) -> Result<ShaderProgram, CardlessError> {
+        let vertex = self.preprocessor.preprocess_file(&self.vertex)?;
+        let fragment = self.preprocessor.preprocess_file(&self.fragment)?;
+
+        // Watch new includes even if the program turns out broken.
+        self.dependencies = vertex.dependencies.iter().chain(&fragment.dependencies).cloned().collect();
+        self.modified = self.poll();
+
+        let vertex = Shader::try_preprocessed(ShaderType::VERTEX, &vertex)?;
+        let fragment = Shader::try_preprocessed(ShaderType::FRAGMENT, &fragment)?;
+        ShaderProgram::try_new(vertex, fragment, layout)
+    }
 }
 
 impl ShaderProgram {
-    pub fn try_new(vertex: Shader, fragment: Shader, layout: &[VertexAttribute]) -> Result<Self, String> {
-        let handler = unsafe { gl::CreateProgram() };
-        unsafe { gl::AttachShader(handler, vertex.handler); }
-        unsafe { gl::AttachShader(handler, fragment.handler); }
-
-
-        unsafe { gl::LinkProgram(handler); }
-
-        let mut success = gl::TRUE as i32;
-        unsafe { gl::GetProgramiv(handler, gl::LINK_STATUS, &mut success); }
-        if success != gl::TRUE as i32 {
-            let mut log: Vec<u8> = Vec::with_capacity(LOG_MAX_LENGTH);
-            unsafe { log.set_len(LOG_MAX_LENGTH); }
-            unsafe { gl::GetProgramInfoLog(handler, LOG_MAX_LENGTH as i32, null_mut(), log.as_mut_ptr() as *mut i8); }
-
-            let log = match std::str::from_utf8(&log) {
-                Ok(s) => s,
-                Err(e) => std::str::from_utf8(&log[..e.valid_up_to()]).unwrap()
-            };
-
-            Err(log.to_string())
-        } else {
-            for (i, attribute) in layout.iter().enumerate() {
-                let normalized = match attribute.normalized {
-                    true => gl::TRUE,
-                    false => gl::FALSE,
-                };
-                match attribute.attribute_type {
-                    VertexAttributeType::F32 => {
-                        let attribute_type = gl::FLOAT;
-                        unsafe {
-                            gl::VertexAttribPointer(
-                                i as u32,
-                                attribute.size as i32,
-                                attribute_type,
-                                normalized,
-                                attribute.stride as i32,
-                                attribute.width as *const c_void,
-                            );
-                        }
-                    }
-                    VertexAttributeType::I32 => {
-                        let attribute_type = gl::INT;
-                        unsafe {
-                            gl::VertexAttribIPointer(
-                                i as u32,
-                                attribute.size as i32,
-                                attribute_type,
-                                attribute.stride as i32,
-                                attribute.width as *const c_void,
-                            );
-                        }
-                    }
-                    VertexAttributeType::U32 => {
-                        let attribute_type = gl::UNSIGNED_INT;
-                        unsafe {
-                            gl::VertexAttribIPointer(
-                                i as u32,
-                                attribute.size as i32,
-                                attribute_type,
-                                attribute.stride as i32,
-                                attribute.width as *const c_void,
-                            );
-                        }
-                    }
-                };
+    /// Links the program and checks its vertex inputs against `layout`,
+    /// see [`vertex_attribute::check_layout`]. Feeding it the vertices is up
+    /// to a [`VertexArray`](super::vertex_array::VertexArray).
+    pub fn try_new(vertex: Shader, fragment: Shader, layout: &[VertexAttribute]) -> Result<Self, CardlessError> {
+        let (handler, active) = device::with(|device| {
+            let handler = device.create_program();
+            device.attach_shader(handler, vertex.handler);
+            device.attach_shader(handler, fragment.handler);
 
-                unsafe { gl::EnableVertexAttribArray(i as u32); }
+            if !device.link_program(handler) {
+                return (handler, Err(device.program_info_log(handler)));
             }
 
-            Ok(Self {
-                handler,
-                uniforms: HashMap::new()
-            })
+            (handler, Ok((device.active_uniforms(handler), device.active_attributes(handler))))
+        });
+
+        let mut program = Self {
+            handler,
+            uniforms: HashMap::new(),
+            active_uniforms: Vec::new(),
+            active_attributes: Vec::new(),
+            files: None,
+            debug: cfg!(debug_assertions),
+            warned: HashSet::new(),
+        };
+        let (active_uniforms, active_attributes) = active.map_err(|log| CardlessError::Link { log })?;
+        vertex_attribute::check_layout(&active_attributes, layout)?;
+
+        program.active_uniforms = active_uniforms;
+        program.active_attributes = active_attributes;
+        Ok(program)
+    }
+
+    /// Builds the program from GLSL files run through `preprocessor`, which
+    /// [`Self::reload_if_changed`] then keeps an eye on, includes as well.
+    pub fn try_from_files<P, Q>(vertex: P, fragment: Q, preprocessor: &Preprocessor, layout: &[VertexAttribute]) -> Result<Self, CardlessError>
+    where P: AsRef<Path>, Q: AsRef<Path> {
+        let mut files = ShaderFiles {
+            vertex: vertex.as_ref().to_path_buf(),
+            fragment: fragment.as_ref().to_path_buf(),
+            preprocessor: preprocessor.clone(),
+            dependencies: vec![vertex.as_ref().to_path_buf(), fragment.as_ref().to_path_buf()],
+            modified: None,
+        };
+
+        let mut program = files.try_link(layout)?;
+        program.files = Some(files);
+
+        Ok(program)
+    }
+
+    /// Recompiles and relinks the program if any of its files changed since
+    /// the last attempt. Returns whether the program was replaced; on error the
+    /// last working program stays in use until the files change again.
+    ///
+    /// Programs not created with [`Self::try_from_files`] never reload.
+    pub fn reload_if_changed(&mut self, layout: &[VertexAttribute]) -> Result<bool, CardlessError> {
+        let files = match &mut self.files {
+            Some(files) => files,
+            None => return Ok(false),
+        };
+
+        // A file missing for a moment is usually an editor replacing it.
+        let modified = match files.poll() {
+            Some(modified) => Some(modified),
+            None => return Ok(false),
+        };
+        if modified == files.modified {
+            return Ok(false);
         }
+        files.modified = modified;
+
+        let mut program = files.try_link(layout)?;
+        std::mem::swap(&mut self.handler, &mut program.handler);
+        std::mem::swap(&mut self.active_uniforms, &mut program.active_uniforms);
+        std::mem::swap(&mut self.active_attributes, &mut program.active_attributes);
+        self.uniforms.clear();
+        self.warned.clear();
+
+        Ok(true)
     }
 
     fn getload_uniform(&mut self, name: &str) -> Option<i32> {
         match self.uniforms.get(name) {
             Some(v) => Some(v.to_owned()),
             None => {
-                let c_name = CString::new(name).unwrap();
-                match unsafe { gl::GetUniformLocation(self.handler, c_name.as_ptr()) } {
-                    -1 => None,
-                    uniform => {
+                match device::with(|device| device.uniform_location(self.handler, name)) {
+                    None => None,
+                    Some(uniform) => {
                         self.uniforms.insert(name.to_string(), uniform);
                         Some(uniform)
                     }
@@ -101,36 +142,64 @@ impl ShaderProgram {
         }
     }
 
-    pub fn set_1iv(&mut self, name: &str, v: &[i32]) {
-        match self.getload_uniform(name) {
-            Some(uniform) => unsafe {gl::Uniform1iv(uniform, v.len() as i32, v.as_ptr()); },
-            None => {}
+    /// Whether [`Self::set_uniform`] and [`Self::bind_uniform_block`] warn on
+    /// stderr about names the program lacks or values of the wrong type, once
+    /// per name. On by default in debug builds.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    fn warn(&mut self, name: &str, warning: String) {
+        if self.warned.insert(name.to_string()) {
+            eprintln!("warning: {}", warning);
         }
     }
 
-    pub fn set_1uv(&mut self, name: &str, v: &[u32]) {
-        match self.getload_uniform(name) {
-            Some(uniform) => unsafe {gl::Uniform1uiv(uniform, v.len() as i32, v.as_ptr()); },
-            None => {}
+    /// Sets a uniform of the active program, e.g. `set_uniform("u_color", &glm::vec4(1., 0., 0., 1.))`
+    /// or `set_uniform("u_texture", &[0, 1, 2])` for an array.
+    pub fn set_uniform<T: Uniform + ?Sized>(&mut self, name: &str, value: &T) {
+        let location = self.getload_uniform(name);
+
+        if self.debug {
+            let active = self.active_uniforms.iter().find(|uniform| uniform.name == name);
+            if let Some(warning) = uniform::check(name, location, active, value) {
+                self.warn(name, warning);
+            }
+        }
+
+        if let Some(location) = location {
+            device::with(|device| value.upload(location, device));
         }
     }
 
+    pub fn set_1iv(&mut self, name: &str, v: &[i32]) {
+        self.set_uniform(name, v);
+    }
+
+    pub fn set_1uv(&mut self, name: &str, v: &[u32]) {
+        self.set_uniform(name, v);
+    }
+
     pub fn set_1i(&mut self, name: &str, v0: i32) {
-        match self.getload_uniform(name) {
-            Some(uniform) => unsafe {gl::Uniform1i(uniform, v0); },
-            None => {}
-        }
+        self.set_uniform(name, &v0);
     }
 
     pub fn set_3f32(&mut self, name: &str, v0: f32, v1: f32, v2: f32) {
-        match self.getload_uniform(name) {
-            Some(uniform) => unsafe {gl::Uniform3f(uniform, v0, v1, v2); },
+        self.set_uniform(name, &glm::vec3(v0, v1, v2));
+    }
+
+    /// Makes the uniform block `name` read from the buffer bound to `binding`
+    /// with [`Buffer::bind_base`](super::buffer::Buffer::bind_base).
+    pub fn bind_uniform_block(&mut self, name: &str, binding: u32) {
+        match device::with(|device| device.uniform_block_index(self.handler, name)) {
+            Some(block) => device::with(|device| device.uniform_block_binding(self.handler, block, binding)),
+            None if self.debug => self.warn(name, format!("uniform block `{}` does not exist or is not used by the shader", name)),
             None => {}
         }
     }
 
     pub fn activate(&mut self) -> &mut Self {
-        unsafe { gl::UseProgram(self.handler); }
+        device::with(|device| device.use_program(self.handler));
 
         self
     }
@@ -138,7 +207,104 @@ impl ShaderProgram {
 
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgram(self.handler); }
+        device::with(|device| device.delete_program(self.handler));
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::{fs::{self, File}, time::{Duration, SystemTime}};
+
+    use crate::cardless::{device::{self, Command, RecordingDevice}, error::CardlessError, shader::{Preprocessor, Shader, ShaderType}, simple2d_renderer::Simple2DVertex, uniform::ActiveUniform, vertex_attribute::{ActiveAttribute, Vertex}};
+
+    use super::ShaderProgram;
+
+    #[test]
+    fn reloads_only_after_files_change() {
+        device::record();
+        let dir = std::env::temp_dir().join(format!("cardless_reload_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vertex = dir.join("shader.vert");
+        let fragment = dir.join("shader.frag");
+        fs::write(&vertex, "void main() {}").unwrap();
+        fs::write(&fragment, "void main() {}").unwrap();
+        fs::write(dir.join("common.glsl"), "float common;").unwrap();
+
+        let mut program = ShaderProgram::try_from_files(&vertex, &fragment, &Preprocessor::new(), &[]).unwrap();
+        let handler = program.handler;
+        assert!(!program.reload_if_changed(&[]).unwrap());
+
+        fs::write(&fragment, "#include \"common.glsl\"\nvoid main() {}").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        File::options().write(true).open(&fragment).unwrap().set_modified(later).unwrap();
+
+        assert!(program.reload_if_changed(&[]).unwrap());
+        assert_ne!(program.handler, handler);
+        assert!(!program.reload_if_changed(&[]).unwrap());
+
+        // Includes are watched too.
+        let handler = program.handler;
+        let later = later + Duration::from_secs(5);
+        File::options().write(true).open(dir.join("common.glsl")).unwrap().set_modified(later).unwrap();
+
+        assert!(program.reload_if_changed(&[]).unwrap());
+        assert_ne!(program.handler, handler);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_file_names_the_path() {
+        device::record();
+
+        match ShaderProgram::try_from_files("missing.vert", "missing.frag", &Preprocessor::new(), &[]) {
+            Err(CardlessError::File { path, .. }) => assert_eq!(path.to_str(), Some("missing.vert")),
+            _ => panic!("expected a file error"),
+        }
+    }
+
+    #[test]
+    fn warns_about_missing_and_mistyped_uniforms() {
+        let color = ActiveUniform { name: "u_color".to_string(), gl_type: gl::FLOAT_VEC4, size: 1, location: Some(3) };
+        let recording = RecordingDevice::new().with_active_uniforms(vec![color]);
+        let log = recording.log();
+        device::set(Box::new(recording));
+
+        let vertex = Shader::try_new(ShaderType::VERTEX, "").unwrap();
+        let fragment = Shader::try_new(ShaderType::FRAGMENT, "").unwrap();
+        let mut program = ShaderProgram::try_new(vertex, fragment, &[]).unwrap();
+        program.set_debug(true);
+
+        log.borrow_mut().clear();
+        program.set_uniform("u_colour", &glm::vec4(1., 0., 0., 1.));
+        assert!(program.warned.contains("u_colour"));
+        assert!(log.borrow().is_empty());
+
+        program.set_uniform("u_color", &1f32);
+        assert!(program.warned.contains("u_color"));
+
+        program.warned.clear();
+        log.borrow_mut().clear();
+        program.set_uniform("u_color", &glm::vec4(1., 0., 0., 1.));
+        assert!(program.warned.is_empty());
+        assert_eq!(*log.borrow(), vec![Command::UniformF32 { location: 3, components: 4, v: vec![1., 0., 0., 1.] }]);
+    }
+
+    #[test]
+    fn linking_checks_the_vertex_layout() {
+        let link = |gl_type: u32| {
+            let position = ActiveAttribute { name: "vert_pos".to_string(), gl_type, size: 1, location: Some(0) };
+            device::set(Box::new(RecordingDevice::new().with_active_attributes(vec![position])));
+
+            let vertex = Shader::try_new(ShaderType::VERTEX, "").unwrap();
+            let fragment = Shader::try_new(ShaderType::FRAGMENT, "").unwrap();
+            ShaderProgram::try_new(vertex, fragment, &Simple2DVertex::get_attributes_layout())
+        };
+
+        assert!(link(gl::FLOAT_VEC2).is_ok());
+        match link(gl::FLOAT_VEC3) {
+            Err(CardlessError::AttributeMismatch { name, location: 0, .. }) => assert_eq!(name, "vert_pos"),
+            _ => panic!("expected an attribute mismatch"),
+        }
+    }
+}
@@ -1,113 +1,229 @@
-use std::{mem::size_of, ptr::null};
+use std::{cell::Cell, cmp::Ordering, path::Path};
 
-use glm::vec2;
-use memoffset::offset_of;
-
-use crate::cardless::vertex_attribute::{VertexAttribute, VertexAttributeType};
-
-use super::{vertex_attribute::Vertex, shader_program::ShaderProgram, batch::Batch, shader::{Shader, ShaderType}, texture::Texture};
+use super::{atlas::{Atlas, AtlasRegion}, camera::{self, Camera2D}, device, error::CardlessError, vertex_attribute::Vertex, shader_program::ShaderProgram, batch::Batch, buffer::StreamingStrategy, sprite::Sprite, shader::{Preprocessor, Shader, ShaderType}, texture::Texture};
 
+#[derive(Vertex)]
 #[repr(C)]
 pub struct Simple2DVertex {
     pub pos: glm::Vec2,
     pub uv: glm::Vec2,
     pub texture: i32,
+    pub color: [u8; 4],
+    /// Clip space depth, from the sprite's place in the draw order.
+    pub z: f32,
 }
 
-impl Vertex for Simple2DVertex {
-    fn get_attributes_layout() -> Vec<VertexAttribute> {
-        vec![
-            VertexAttribute::new(VertexAttributeType::F32, 2, false, size_of::<Self>(), offset_of!(Self, pos)),
-            VertexAttribute::new(VertexAttributeType::F32, 2, false, size_of::<Self>(), offset_of!(Self, uv)),
-            VertexAttribute::new(VertexAttributeType::I32, 1, false, size_of::<Self>(), offset_of!(Self, texture)),
-        ]
-    }
+/// Sprites a frame can give depths of their own, later ones sharing the
+/// nearest. 24 bit depth buffers keep them 16 levels apart.
+const DEPTH_STEPS: u32 = 1 << 20;
+
+thread_local! {
+    /// Depth clears seen last and depth steps taken since, shared by every
+    /// renderer so that their flushes stack up in order.
+    static FRAME_DEPTH: Cell<(u64, u32)> = const { Cell::new((0, 0)) };
 }
 
 pub struct BatchRenderer {
     shader: ShaderProgram,
     batch: Batch<Simple2DVertex>,
+    view_projection: glm::Mat4,
+    queue: Vec<QueuedQuad>,
 }
 
 impl BatchRenderer {
-    pub fn new(fragment: &str, vertex: &str) -> Self {
-        let batch = Batch::new();
-        let fragment = Shader::try_new(ShaderType::FRAGMENT, fragment).unwrap();
-        let vertex = Shader::try_new(ShaderType::VERTEX, vertex).unwrap();
-        let shader = ShaderProgram::try_new(vertex, fragment, &Simple2DVertex::get_attributes_layout()).unwrap();
+    /// Shaders may include `cardless/textures.glsl` for `sample_texture`,
+    /// which covers as many slots as the batch has.
+    pub fn try_new(fragment: &str, vertex: &str) -> Result<Self, CardlessError> {
+        let batch = Batch::try_new()?;
+        let preprocessor = Preprocessor::new().with_texture_slots(batch.textures_capacity);
+        let fragment = Shader::try_preprocessed(ShaderType::FRAGMENT, &preprocessor.preprocess(fragment)?)?;
+        let vertex = Shader::try_preprocessed(ShaderType::VERTEX, &preprocessor.preprocess(vertex)?)?;
+        let shader = ShaderProgram::try_new(vertex, fragment, &Simple2DVertex::get_attributes_layout())?;
+
+        Ok(Self::with_program(batch, shader))
+    }
+
+    /// Like [`Self::try_new`], with shaders preprocessed already, e.g. the
+    /// ones of a [`Bundle`](super::bundle::Bundle), which are compiled as
+    /// they are.
+    pub fn try_from_preprocessed(fragment: &str, vertex: &str) -> Result<Self, CardlessError> {
+        let batch = Batch::try_new()?;
+        let fragment = Shader::try_new(ShaderType::FRAGMENT, fragment)?;
+        let vertex = Shader::try_new(ShaderType::VERTEX, vertex)?;
+        let shader = ShaderProgram::try_new(vertex, fragment, &Simple2DVertex::get_attributes_layout())?;
+
+        Ok(Self::with_program(batch, shader))
+    }
+
+    /// Like [`Self::try_new`], but with shaders read from files that can be
+    /// edited while the game runs, see [`Self::reload_shaders`].
+    pub fn try_from_files<P, Q>(fragment: P, vertex: Q) -> Result<Self, CardlessError>
+    where P: AsRef<Path>, Q: AsRef<Path> {
+        let batch = Batch::try_new()?;
+        let preprocessor = Preprocessor::new().with_texture_slots(batch.textures_capacity);
+        let shader = ShaderProgram::try_from_files(vertex, fragment, &preprocessor, &Simple2DVertex::get_attributes_layout())?;
+
+        Ok(Self::with_program(batch, shader))
+    }
 
+    fn with_program(batch: Batch<Simple2DVertex>, shader: ShaderProgram) -> Self {
         Self {
             shader,
             batch,
+            view_projection: camera::identity(),
+            queue: Vec::new(),
         }
     }
 
+    /// Picks up edited shader files, meant to be called once per frame.
+    /// If the new version fails to build, the previous one keeps rendering.
+    pub fn reload_shaders(&mut self) -> Result<bool, CardlessError> {
+        if !self.shader.reload_if_changed(&Simple2DVertex::get_attributes_layout())? {
+            return Ok(false);
+        }
+
+        self.bind();
+        Ok(true)
+    }
+
+    /// See [`Batch::set_streaming`].
+    pub fn set_streaming(&mut self, strategy: StreamingStrategy) -> Result<(), CardlessError> {
+        self.batch.set_streaming(strategy)
+    }
+
     pub fn bind(&mut self) {
         self.shader.activate();
-        self.shader.set_1iv("u_texture", &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        let slots: Vec<i32> = (0..self.batch.textures_capacity as i32).collect();
+        self.shader.set_uniform("u_texture", &slots);
+        self.shader.set_uniform("u_view_projection", &self.view_projection);
     }
 
-    pub fn push_square(&mut self, pos: glm::Vec2, size: glm::Vec2) {
-        if self.batch.vbo.data.len() + 4 > self.batch.vbo_capacity 
-        || self.batch.ebo.data.len() + 6 > self.batch.ebo_capacity {
-            self.flush()
+    /// Matrix positions are transformed by in the vertex shader, identity by
+    /// default so they are normalised device coordinates. Quads pushed
+    /// before are drawn with the previous one.
+    pub fn set_view_projection(&mut self, view_projection: glm::Mat4) {
+        if !self.queue.is_empty() {
+            self.flush();
         }
+        self.view_projection = view_projection;
+        self.shader.activate().set_uniform("u_view_projection", &view_projection);
+    }
 
-        let first_vertex = self.batch.vbo.data.len() as u32;
+    /// Draws what follows through `camera`, see [`Self::set_view_projection`].
+    pub fn set_camera(&mut self, camera: &Camera2D) {
+        self.set_view_projection(camera.view_projection());
+    }
 
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + 0., uv: glm::vec2(0., 0.), texture: 0});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + vec2(size.x, 0.), uv: glm::vec2(1., 0.), texture: 0});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + vec2(0., size.y), uv: glm::vec2(0., 1.), texture: 0});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + size, uv: glm::vec2(1., 1.), texture: 0});
+    pub fn push_square(&mut self, pos: glm::Vec2, size: glm::Vec2) {
+        self.queue_quad(&Sprite::new(pos, size), None, true);
+    }
 
-        self.batch.ebo.data.push(first_vertex + 0);
-        self.batch.ebo.data.push(first_vertex + 1);
-        self.batch.ebo.data.push(first_vertex + 2);
-        self.batch.ebo.data.push(first_vertex + 2);
-        self.batch.ebo.data.push(first_vertex + 1);
-        self.batch.ebo.data.push(first_vertex + 3);
+    pub fn push_square_texture(&mut self, pos: glm::Vec2, size: glm::Vec2, texture: &Texture) {
+        self.draw_sprite(&Sprite::new(pos, size), texture);
+    }
 
+    /// Queues `sprite` until the next [`Self::flush`].
+    pub fn draw_sprite(&mut self, sprite: &Sprite, texture: &Texture) {
+        self.queue_quad(sprite, Some(texture.handler), texture.opaque);
     }
 
-    pub fn push_square_texture(&mut self, pos: glm::Vec2, size: glm::Vec2, texture: &Texture) {
-        if self.batch.vbo.data.len() + 4 > self.batch.vbo_capacity 
-        || self.batch.ebo.data.len() + 6 > self.batch.ebo_capacity {
-            self.flush()
+    /// Like [`Self::draw_sprite`] with the page of `region`, the sprite being
+    /// opaque if the region's image is rather than the whole page.
+    pub fn draw_region(&mut self, sprite: &Sprite, atlas: &Atlas, region: &AtlasRegion) {
+        self.queue_quad(sprite, Some(atlas.page(region).handler), region.opaque);
+    }
+
+    fn queue_quad(&mut self, sprite: &Sprite, texture: Option<u32>, opaque: bool) {
+        let translucent = sprite.color[3] < 255 || !opaque;
+        self.queue.push(QueuedQuad { sprite: *sprite, texture, translucent, z: 0. });
+    }
+
+    /// Draws the queued sprites by layer, then depth, then push order.
+    ///
+    /// If the bound target has a depth buffer, opaque sprites are sorted by
+    /// layer, texture and depth to need as few texture slots as possible and
+    /// placed by the depth buffer. Translucent ones follow, back to front.
+    /// Without one, every sprite is drawn back to front.
+    ///
+    /// Sprites of separate flushes, of any renderer, are drawn in flush order
+    /// until depth is cleared, which should happen once per frame, e.g. with
+    /// [`Framebuffer::clear`](super::framebuffer::Framebuffer::clear).
+    pub fn flush(&mut self) {
+        if self.queue.is_empty() {
+            return;
         }
 
-        let texture = match self.batch.get_texture_slot(texture) {
-            Some(slot) => slot,
-            None => {
-                self.flush();
-                self.batch.get_texture_slot(texture).unwrap()
+        let depth = device::with(|device| device.has_depth());
+        let first = reserve_depth(self.queue.len());
+        let (opaque, translucent) = sort_quads(std::mem::take(&mut self.queue), depth, first);
+
+        self.shader.activate();
+        self.batch.vertex_array.bind();
+        if !depth {
+            self.push_quads(&translucent);
+            return;
+        }
+
+        let (depth_test, depth_write) = device::with(|device| {
+            let previous = (device.get_depth_test(), device.get_depth_write());
+            device.depth_write(true);
+            device.depth_test(true);
+            previous
+        });
+        self.push_quads(&opaque);
+        device::with(|device| device.depth_write(false));
+        self.push_quads(&translucent);
+        device::with(|device| {
+            device.depth_write(depth_write);
+            device.depth_test(depth_test);
+        });
+    }
+
+    /// Fills the batch with `quads` in order, drawing whenever it is full.
+    fn push_quads(&mut self, quads: &[QueuedQuad]) {
+        for quad in quads {
+            if self.batch.vbo.data.len() + 4 > self.batch.vbo_capacity
+            || self.batch.ebo.data.len() + 6 > self.batch.ebo_capacity {
+                self.draw();
             }
-        };
 
-        let first_vertex = self.batch.vbo.data.len() as u32;
+            let texture = match quad.texture {
+                Some(texture) => match self.batch.get_texture_slot(texture) {
+                    Some(slot) => slot,
+                    None => {
+                        self.draw();
+                        self.batch.get_texture_slot(texture).unwrap()
+                    }
+                },
+                None => 0,
+            };
 
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + 0., uv: glm::vec2(0., 0.), texture});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + vec2(size.x, 0.), uv: glm::vec2(1., 0.), texture});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + vec2(0., size.y), uv: glm::vec2(0., 1.), texture});
-        self.batch.vbo.data.push(Simple2DVertex { pos: pos + size, uv: glm::vec2(1., 1.), texture});
+            let first_vertex = self.batch.vbo.data.len() as u32;
+            for &(pos, uv) in &quad.sprite.corners() {
+                self.batch.vbo.data.push(Simple2DVertex { pos, uv, texture, color: quad.sprite.color, z: quad.z });
+            }
+            for &index in &[0, 1, 2, 2, 1, 3] {
+                self.batch.ebo.data.push(first_vertex + index);
+            }
+        }
 
-        self.batch.ebo.data.push(first_vertex + 0);
-        self.batch.ebo.data.push(first_vertex + 1);
-        self.batch.ebo.data.push(first_vertex + 2);
-        self.batch.ebo.data.push(first_vertex + 2);
-        self.batch.ebo.data.push(first_vertex + 1);
-        self.batch.ebo.data.push(first_vertex + 3);
+        if !self.batch.ebo.data.is_empty() {
+            self.draw();
+        }
     }
 
-    pub fn flush(&mut self) {
-        self.batch.vbo.flush();
-        self.batch.ebo.flush();
+    fn draw(&mut self) {
+        let base_vertex = self.batch.vbo.flush();
+        let first = self.batch.ebo.flush();
 
-        for (slot, &texture) in self.batch.textures.iter().enumerate() {
-            unsafe { gl::ActiveTexture(gl::TEXTURE0 + slot as u32); }
-            unsafe { gl::BindTexture(gl::TEXTURE_2D, texture); }
-        }
+        device::with(|device| {
+            for (slot, &texture) in self.batch.textures.iter().enumerate() {
+                device.active_texture(slot as u32);
+                device.bind_texture(texture);
+            }
 
-        unsafe { gl::DrawElements(gl::TRIANGLES, self.batch.ebo.data.len() as i32, gl::UNSIGNED_INT, null()); }
+            device.draw_elements(self.batch.ebo.data.len(), first, base_vertex);
+        });
 
         if self.batch.textures.len() == self.batch.textures_capacity {
             self.batch.textures.clear();
@@ -117,3 +233,324 @@ impl BatchRenderer {
     }
 }
 
+/// Sprite waiting for [`BatchRenderer::flush`].
+struct QueuedQuad {
+    sprite: Sprite,
+    texture: Option<u32>,
+    translucent: bool,
+    /// Clip space depth, assigned on flush.
+    z: f32,
+}
+
+/// Takes `count` depth steps after the ones taken since depth was last
+/// cleared, returning the first.
+fn reserve_depth(count: usize) -> u32 {
+    let clears = device::with(|device| device.depth_clears());
+    FRAME_DEPTH.with(|frame| {
+        let (seen, taken) = frame.get();
+        let first = if seen == clears { taken } else { 0 };
+        frame.set((clears, first.saturating_add(count as u32)));
+        first
+    })
+}
+
+/// Splits `queue` into opaque quads in drawing order and translucent ones
+/// back to front, each given the depth of its place in painter's order,
+/// counted from the depth step `first`. Without a `depth` buffer to place
+/// the opaque ones, every quad is returned back to front with the
+/// translucent ones.
+fn sort_quads(mut queue: Vec<QueuedQuad>, depth: bool, first: u32) -> (Vec<QueuedQuad>, Vec<QueuedQuad>) {
+    // From the far plane towards the camera, the last quad nearest.
+    let mut painter: Vec<usize> = (0..queue.len()).collect();
+    painter.sort_by(|&a, &b| painter_order(&queue[a], &queue[b]));
+    for (rank, &index) in painter.iter().enumerate() {
+        let step = first.saturating_add(rank as u32).min(DEPTH_STEPS - 1);
+        queue[index].z = 1. - 2. * (step + 1) as f32 / DEPTH_STEPS as f32;
+    }
+
+    if !depth {
+        queue.sort_by(painter_order);
+        return (Vec::new(), queue);
+    }
+
+    let (mut translucent, mut opaque): (Vec<QueuedQuad>, Vec<QueuedQuad>) = queue.into_iter().partition(|quad| quad.translucent);
+    opaque.sort_by(|a, b| a.sprite.layer.cmp(&b.sprite.layer)
+        .then_with(|| a.texture.cmp(&b.texture))
+        .then_with(|| a.sprite.depth.total_cmp(&b.sprite.depth)));
+    translucent.sort_by(painter_order);
+
+    (opaque, translucent)
+}
+
+/// Back to front, to be sorted stably so equal keys keep their push order.
+fn painter_order(a: &QueuedQuad, b: &QueuedQuad) -> Ordering {
+    a.sprite.layer.cmp(&b.sprite.layer).then_with(|| a.sprite.depth.total_cmp(&b.sprite.depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glm::vec2;
+    use image::RgbaImage;
+
+    use crate::cardless::{atlas::AtlasBuilder, device::{self, Command, RecordingDevice}, framebuffer::Framebuffer, sprite::Sprite, texture::Texture};
+
+    use super::{reserve_depth, sort_quads, BatchRenderer, QueuedQuad};
+
+    fn draws(log: &Rc<RefCell<Vec<Command>>>) -> Vec<usize> {
+        log.borrow().iter().filter_map(|command| match command {
+            Command::DrawElements { count, .. } => Some(*count),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn flush_draws_all_squares_at_once() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let texture_a = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+        let texture_b = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+
+        br.push_square_texture(vec2(0., 0.), vec2(1., 1.), &texture_a);
+        br.push_square_texture(vec2(1., 0.), vec2(1., 1.), &texture_b);
+        br.push_square_texture(vec2(2., 0.), vec2(1., 1.), &texture_a);
+        assert!(draws(&log).is_empty());
+
+        log.borrow_mut().clear();
+        br.flush();
+
+        assert_eq!(draws(&log), vec![18]);
+        let log = log.borrow();
+        assert!(log.contains(&Command::ActiveTexture(0)));
+        assert!(log.contains(&Command::BindTexture(texture_a.handler)));
+        assert!(log.contains(&Command::ActiveTexture(1)));
+        assert!(log.contains(&Command::BindTexture(texture_b.handler)));
+        assert!(!log.contains(&Command::ActiveTexture(2)));
+    }
+
+    #[test]
+    fn texture_slot_overflow_splits_the_flush() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let textures: Vec<Texture> = (0..17).map(|_| Texture::try_from_rgba(1, 1, &[0; 4]).unwrap()).collect();
+
+        for texture in &textures {
+            br.push_square_texture(vec2(0., 0.), vec2(1., 1.), texture);
+        }
+        assert!(draws(&log).is_empty());
+
+        br.flush();
+        assert_eq!(draws(&log), vec![16 * 6, 6]);
+    }
+
+    #[test]
+    fn each_renderer_draws_from_its_own_vertex_array() {
+        let log = device::record();
+        let mut first = BatchRenderer::try_new("", "").unwrap();
+        let mut second = BatchRenderer::try_new("", "").unwrap();
+        let vertex_arrays: Vec<u32> = log.borrow().iter().filter_map(|command| match command {
+            Command::CreateVertexArray(vertex_array) => Some(*vertex_array),
+            _ => None,
+        }).collect();
+        assert_eq!(vertex_arrays.len(), 2);
+
+        for (br, &vertex_array) in [&mut first, &mut second].iter_mut().zip(&vertex_arrays) {
+            log.borrow_mut().clear();
+            br.push_square(vec2(0., 0.), vec2(1., 1.));
+            br.flush();
+
+            let log = log.borrow();
+            let bind = log.iter().position(|command| *command == Command::BindVertexArray(vertex_array)).unwrap();
+            let draw = log.iter().position(|command| matches!(command, Command::DrawElements { .. })).unwrap();
+            assert!(bind < draw);
+        }
+    }
+
+    #[test]
+    fn texture_slots_follow_the_driver() {
+        let recording = RecordingDevice::new().with_texture_units(8);
+        let log = recording.log();
+        device::set(Box::new(recording));
+
+        let mut br = BatchRenderer::try_new("#include \"cardless/textures.glsl\"", "").unwrap();
+        br.bind();
+
+        let log = log.borrow();
+        assert!(log.iter().any(|command| match command {
+            Command::CompileShader { source, .. } => source.contains("uniform sampler2D u_texture[8];"),
+            _ => false,
+        }));
+        assert!(log.iter().any(|command| match command {
+            Command::UniformI32 { v, .. } => *v == (0..8).collect::<Vec<i32>>(),
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn preprocessed_shaders_compile_as_they_are() {
+        let log = device::record();
+        BatchRenderer::try_from_preprocessed("#version 330 core\nvoid main() {}\n", "#version 330 core\n").unwrap();
+
+        let sources: Vec<String> = log.borrow().iter().filter_map(|command| match command {
+            Command::CompileShader { source, .. } => Some(source.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(sources, vec!["#version 330 core\nvoid main() {}\n", "#version 330 core\n"]);
+    }
+
+    #[test]
+    fn running_out_of_indices_flushes() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+
+        // 2048 indices hold 341 squares
+        for _ in 0..342 {
+            br.push_square(vec2(0., 0.), vec2(1., 1.));
+        }
+        br.flush();
+
+        assert_eq!(draws(&log), vec![341 * 6, 6]);
+    }
+
+    #[test]
+    fn sprites_share_the_batch_with_squares() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let texture = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+
+        br.push_square_texture(vec2(0., 0.), vec2(1., 1.), &texture);
+        br.draw_sprite(&Sprite::new(vec2(1., 0.), vec2(1., 1.)).with_color([255, 0, 0, 128]).with_flip(true, false), &texture);
+
+        assert_eq!(br.queue[0].sprite.color, [255; 4]);
+        assert_eq!(br.queue[1].sprite.color, [255, 0, 0, 128]);
+        assert_eq!(br.queue[1].texture, Some(texture.handler));
+
+        br.flush();
+        assert_eq!(draws(&log), vec![12]);
+    }
+
+    #[test]
+    fn opacity_comes_from_the_color_and_what_is_drawn() {
+        device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let mut builder = AtlasBuilder::new(16);
+        builder.add("solid", RgbaImage::from_pixel(2, 2, image::Rgba([255; 4])));
+        let atlas = builder.try_build().unwrap();
+        let region = atlas.region("solid").unwrap();
+        let clear = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap().with_opaque(true);
+        let sprite = Sprite::new(vec2(0., 0.), vec2(1., 1.)).with_source(region.rect);
+
+        br.push_square(vec2(0., 0.), vec2(1., 1.));
+        br.draw_region(&sprite, &atlas, region);
+        br.draw_sprite(&sprite, atlas.page(region));
+        br.draw_sprite(&sprite, &clear);
+        br.draw_sprite(&sprite.with_color([255, 255, 255, 254]), &clear);
+
+        let translucent: Vec<bool> = br.queue.iter().map(|quad| quad.translucent).collect();
+        assert_eq!(translucent, vec![false, false, true, false, true]);
+    }
+
+    #[test]
+    fn sorts_by_layer_then_texture_then_depth() {
+        let quad = |x: f32, layer: i32, depth: f32, texture: u32, translucent: bool| QueuedQuad {
+            sprite: Sprite::new(vec2(x, 0.), vec2(1., 1.)).with_layer(layer).with_depth(depth),
+            texture: Some(texture),
+            translucent,
+            z: 0.,
+        };
+        let queue = vec![
+            quad(0., 1, 0., 2, false),
+            quad(1., 0, 0.5, 2, true),
+            quad(2., 0, 0., 3, false),
+            quad(3., 1, 0., 1, false),
+            quad(4., 0, 0.5, 1, true),
+            quad(5., 0, 0.2, 2, false),
+            quad(6., -1, 0., 1, true),
+        ];
+        let (opaque, translucent) = sort_quads(queue, true, 0);
+
+        let order = |quads: &[QueuedQuad]| quads.iter().map(|quad| quad.sprite.position.x as usize).collect::<Vec<_>>();
+        assert_eq!(order(&opaque), vec![5, 2, 3, 0]);
+        assert_eq!(order(&translucent), vec![6, 1, 4]);
+
+        // Nearer the camera the later in painter's order, equal keys by push order.
+        let z = |x: usize| opaque.iter().chain(&translucent).find(|quad| quad.sprite.position.x as usize == x).unwrap().z;
+        let painter = [6, 2, 5, 1, 4, 0, 3];
+        for pair in painter.windows(2) {
+            assert!(z(pair[0]) > z(pair[1]));
+        }
+        assert!(z(6) < 1. && z(3) > -1.);
+    }
+
+    fn bound_textures(log: &Rc<RefCell<Vec<Command>>>) -> Vec<u32> {
+        log.borrow().iter().filter_map(|command| match command {
+            Command::BindTexture(texture) => Some(*texture),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn depthless_targets_draw_in_painter_order() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let texture_a = Texture::try_from_rgba(1, 1, &[255; 4]).unwrap();
+        let texture_b = Texture::try_from_rgba(1, 1, &[255; 4]).unwrap();
+        let target = Framebuffer::try_new(4, 4, false).unwrap();
+
+        target.render(|| {
+            br.push_square_texture(vec2(0., 0.), vec2(1., 1.), &texture_b);
+            br.push_square_texture(vec2(0.5, 0.), vec2(1., 1.), &texture_a);
+            log.borrow_mut().clear();
+            br.flush();
+        });
+
+        assert_eq!(bound_textures(&log), vec![texture_b.handler, texture_a.handler]);
+        assert!(!log.borrow().iter().any(|command| matches!(command, Command::ClearDepth | Command::DepthTest(_) | Command::DepthWrite(_))));
+    }
+
+    #[test]
+    fn flush_restores_the_depth_state() {
+        let log = device::record();
+        let mut br = BatchRenderer::try_new("", "").unwrap();
+        let texture_a = Texture::try_from_rgba(1, 1, &[255; 4]).unwrap();
+        let texture_b = Texture::try_from_rgba(1, 1, &[255; 4]).unwrap();
+        device::with(|device| {
+            device.depth_test(true);
+            device.depth_write(false);
+        });
+
+        log.borrow_mut().clear();
+        br.flush();
+        assert!(log.borrow().is_empty());
+
+        br.push_square_texture(vec2(0., 0.), vec2(1., 1.), &texture_b);
+        br.push_square_texture(vec2(0.5, 0.), vec2(1., 1.), &texture_a);
+        br.flush();
+
+        // Sorted by texture, the depth buffer keeping the later square on top.
+        assert_eq!(bound_textures(&log), vec![texture_a.handler, texture_b.handler]);
+        let log = log.borrow();
+        assert!(!log.contains(&Command::ClearDepth));
+        assert_eq!(log[log.len() - 2..], [Command::DepthWrite(false), Command::DepthTest(true)]);
+    }
+
+    #[test]
+    fn renderers_share_the_depth_range_until_it_is_cleared() {
+        device::record();
+        let mut first = BatchRenderer::try_new("", "").unwrap();
+        let mut second = BatchRenderer::try_new("", "").unwrap();
+
+        first.push_square(vec2(0., 0.), vec2(1., 1.));
+        first.push_square(vec2(1., 0.), vec2(1., 1.));
+        first.flush();
+        second.push_square(vec2(0., 0.), vec2(1., 1.));
+        second.flush();
+        assert_eq!(reserve_depth(1), 3);
+
+        device::with(|device| device.clear([0.; 4]));
+        second.push_square(vec2(0., 0.), vec2(1., 1.));
+        second.flush();
+        assert_eq!(reserve_depth(1), 1);
+    }
+}
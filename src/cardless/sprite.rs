@@ -0,0 +1,182 @@
+/// Axis-aligned rectangle given by its bottom-left corner and size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub position: glm::Vec2,
+    pub size: glm::Vec2,
+}
+
+impl Rect {
+    pub fn new(position: glm::Vec2, size: glm::Vec2) -> Self {
+        Self { position, size }
+    }
+}
+
+/// A textured quad as drawn by
+/// [`BatchRenderer::draw_sprite`](super::simple2d_renderer::BatchRenderer::draw_sprite).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// Where the origin ends up.
+    pub position: glm::Vec2,
+    pub size: glm::Vec2,
+    /// Point the sprite is placed, scaled and rotated by, as a fraction of
+    /// its size: `(0, 0)` is the bottom-left corner, `(0.5, 0.5)` the centre.
+    pub origin: glm::Vec2,
+    /// Counter-clockwise, in radians.
+    pub rotation: f32,
+    pub scale: glm::Vec2,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Multiplies the sampled colour.
+    pub color: [u8; 4],
+    /// Part of the texture shown, in UV space.
+    pub source: Rect,
+    /// Sprites on higher layers are drawn over the ones below, see
+    /// [`BatchRenderer::flush`](super::simple2d_renderer::BatchRenderer::flush).
+    pub layer: i32,
+    /// Order within the layer, higher in front. Sprites with equal layer
+    /// and depth are drawn in the order they were pushed.
+    pub depth: f32,
+}
+
+impl Sprite {
+    /// Untinted sprite of the whole texture with its bottom-left corner at `position`.
+    pub fn new(position: glm::Vec2, size: glm::Vec2) -> Self {
+        Self {
+            position,
+            size,
+            origin: glm::vec2(0., 0.),
+            rotation: 0.,
+            scale: glm::vec2(1., 1.),
+            flip_x: false,
+            flip_y: false,
+            color: [255; 4],
+            source: Rect::new(glm::vec2(0., 0.), glm::vec2(1., 1.)),
+            layer: 0,
+            depth: 0.,
+        }
+    }
+
+    pub fn with_origin(mut self, origin: glm::Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: glm::Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_source(mut self, source: Rect) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Columns of the matrix taking the unit square to the sprite's extent,
+    /// before translation.
+    pub fn transform(&self) -> (glm::Vec2, glm::Vec2) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = self.size.x * self.scale.x;
+        let y = self.size.y * self.scale.y;
+
+        (glm::vec2(cos * x, sin * x), glm::vec2(-sin * y, cos * y))
+    }
+
+    /// Where the unit square's `(0, 0)` corner lands.
+    pub fn translation(&self) -> glm::Vec2 {
+        let (x, y) = self.transform();
+        self.position - x * self.origin.x - y * self.origin.y
+    }
+
+    /// Offset and size of the sampled area, negative sizes flipping it.
+    pub fn uv_rect(&self) -> Rect {
+        let mut uv = self.source;
+        if self.flip_x {
+            uv.position.x += uv.size.x;
+            uv.size.x = -uv.size.x;
+        }
+        if self.flip_y {
+            uv.position.y += uv.size.y;
+            uv.size.y = -uv.size.y;
+        }
+        uv
+    }
+
+    /// Position and UV of the bottom-left, bottom-right, top-left and
+    /// top-right corners.
+    pub fn corners(&self) -> [(glm::Vec2, glm::Vec2); 4] {
+        let (x, y) = self.transform();
+        let translation = self.translation();
+        let uv = self.uv_rect();
+
+        let corner = |u: f32, v: f32| (
+            translation + x * u + y * v,
+            uv.position + glm::vec2(uv.size.x * u, uv.size.y * v),
+        );
+        [corner(0., 0.), corner(1., 0.), corner(0., 1.), corner(1., 1.)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glm::vec2;
+
+    use super::{Rect, Sprite};
+
+    fn assert_near(a: glm::Vec2, b: glm::Vec2) {
+        assert!((a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotates_and_scales_around_the_origin() {
+        let sprite = Sprite::new(vec2(1., 1.), vec2(2., 1.))
+            .with_origin(vec2(0.5, 0.5))
+            .with_scale(vec2(2., 2.))
+            .with_rotation(FRAC_PI_2);
+        let corners = sprite.corners();
+
+        assert_near(corners[0].0, vec2(2., -1.));
+        assert_near(corners[1].0, vec2(2., 3.));
+        assert_near(corners[2].0, vec2(0., -1.));
+        assert_near(corners[3].0, vec2(0., 3.));
+    }
+
+    #[test]
+    fn flips_the_source_rect() {
+        let sprite = Sprite::new(vec2(0., 0.), vec2(1., 1.))
+            .with_source(Rect::new(vec2(0.5, 0.), vec2(0.25, 0.5)))
+            .with_flip(true, false);
+        let corners = sprite.corners();
+
+        assert_near(corners[0].1, vec2(0.75, 0.));
+        assert_near(corners[1].1, vec2(0.5, 0.));
+        assert_near(corners[3].1, vec2(0.5, 0.5));
+    }
+}
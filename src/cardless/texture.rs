@@ -1,42 +1,435 @@
-use std::{io::{BufRead, Seek}, ffi::c_void};
-use image::GenericImageView;
+use std::io::{BufRead, Seek};
+use image::{DynamicImage, GenericImageView};
+
+use super::{device::{self, RenderDevice}, error::{CardlessError, check_gl}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// What sampling outside `0..1` UVs reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    /// The edge pixels.
+    Clamp,
+    /// [`TextureOptions::border_color`].
+    Border,
+    Repeat,
+    /// Every other repetition flipped.
+    Mirror,
+}
+
+/// How a texture is sampled. The default, used by the constructors without
+/// options, repeats, smooths minified textures between mipmaps and keeps
+/// magnified pixels sharp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub mipmaps: bool,
+    /// Anisotropic filtering level, 1 for none. Clamped to what the driver
+    /// supports.
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Nearest,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            mipmaps: true,
+            anisotropy: 1.,
+            border_color: [0.; 4],
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Crisp and clamped, for pixel art and UI.
+    pub fn pixel_art() -> Self {
+        Self::new().with_filter(Filter::Nearest).with_wrap(Wrap::Clamp).with_mipmaps(false)
+    }
+
+    /// Both minification and magnification filter.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn with_min_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn with_mag_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    /// Both horizontal and vertical wrapping.
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self
+    }
+
+    pub fn with_wrap_st(mut self, wrap_s: Wrap, wrap_t: Wrap) -> Self {
+        self.wrap_s = wrap_s;
+        self.wrap_t = wrap_t;
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Colour read outside the texture with [`Wrap::Border`].
+    pub fn with_border_color(mut self, border_color: [f32; 4]) -> Self {
+        self.border_color = border_color;
+        self
+    }
+
+    /// Sets the sampling state of the bound texture, generating its mipmaps
+    /// if enabled.
+    fn apply(&self, device: &mut dyn RenderDevice) {
+        let min_filter = match (self.min_filter, self.mipmaps) {
+            (Filter::Nearest, false) => gl::NEAREST,
+            (Filter::Linear, false) => gl::LINEAR,
+            (Filter::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+        let wrap = |wrap| match wrap {
+            Wrap::Clamp => gl::CLAMP_TO_EDGE,
+            Wrap::Border => gl::CLAMP_TO_BORDER,
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::Mirror => gl::MIRRORED_REPEAT,
+        };
+
+        device.texture_parameter(gl::TEXTURE_WRAP_S, wrap(self.wrap_s) as i32);
+        device.texture_parameter(gl::TEXTURE_WRAP_T, wrap(self.wrap_t) as i32);
+        device.texture_parameter(gl::TEXTURE_MIN_FILTER, min_filter as i32);
+        device.texture_parameter(gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+        device.texture_parameter_f32(gl::TEXTURE_BORDER_COLOR, &self.border_color);
+
+        // Drivers without the extension reject the parameter altogether.
+        let max_anisotropy = device.max_anisotropy();
+        if max_anisotropy > 1. {
+            device.texture_parameter_f32(device::TEXTURE_MAX_ANISOTROPY, &[self.anisotropy.max(1.).min(max_anisotropy)]);
+        }
+
+        if self.mipmaps {
+            device.generate_mipmap();
+        }
+    }
+}
 
 pub struct Texture {
     pub handler: u32,
+    pub width: u32,
+    pub height: u32,
+    /// `gl::RGBA8` or `gl::R8`.
+    pub internal_format: u32,
+    /// Whether every pixel has full alpha, which lets batches reorder the
+    /// sprites drawn with it. Empty and `R8` textures never are.
+    pub opaque: bool,
+    mipmaps: bool,
 }
 
 impl Texture {
-    pub fn try_load<T>(data: T) -> Option<Self>
+    /// Decodes an image in any format the `image` crate detects from its content.
+    pub fn try_load<T>(data: T) -> Result<Self, CardlessError>
     where T: BufRead + Seek {
-        let image = image::load(data, image::ImageFormat::Png).unwrap().flipv();
-        let mut handler = 0;
-        unsafe { gl::GenTextures(1, &mut handler); }
-        unsafe { gl::BindTexture(gl::TEXTURE_2D, handler); }
-        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32); }
-        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32); }
-        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32); }
-        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32); }
-
-        if let Some(colors) = image.as_rgba8() {
-            unsafe { gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, image.width() as i32, image.height() as i32, 0, gl::RGBA, gl::UNSIGNED_BYTE, colors.as_ptr() as *const c_void); }
-        }
-        if let Some(colors) = image.as_rgb8() {
-            unsafe { gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, image.width() as i32, image.height() as i32, 0, gl::RGB, gl::UNSIGNED_BYTE, colors.as_ptr() as *const c_void); }
+        Self::try_load_with(data, &TextureOptions::default())
+    }
+
+    pub fn try_load_with<T>(data: T, options: &TextureOptions) -> Result<Self, CardlessError>
+    where T: BufRead + Seek {
+        let image = image::io::Reader::new(data).with_guessed_format()?.decode()?;
+        Self::try_from_image_with(&image, options)
+    }
+
+    /// Uploads an image stored top row first, as image files are. 8-bit RGB
+    /// and RGBA images go as they are, anything else is converted to 8-bit RGBA.
+    pub fn try_from_image(image: &DynamicImage) -> Result<Self, CardlessError> {
+        Self::try_from_image_with(image, &TextureOptions::default())
+    }
+
+    pub fn try_from_image_with(image: &DynamicImage, options: &TextureOptions) -> Result<Self, CardlessError> {
+        let image = image.flipv();
+        let (width, height) = image.dimensions();
+
+        match &image {
+            DynamicImage::ImageRgba8(pixels) => Self::try_upload(gl::RGBA8, width, height, gl::RGBA, pixels, options),
+            DynamicImage::ImageRgb8(pixels) => Self::try_upload(gl::RGBA8, width, height, gl::RGB, pixels, options),
+            image => Self::try_upload(gl::RGBA8, width, height, gl::RGBA, &image.to_rgba8(), options),
         }
+    }
+
+    /// Texture from 8-bit RGBA pixels, bottom row first as OpenGL expects.
+    pub fn try_from_rgba(width: u32, height: u32, pixels: &[u8]) -> Result<Self, CardlessError> {
+        check_pixels(width, height, 4, pixels)?;
+        Self::try_upload(gl::RGBA8, width, height, gl::RGBA, pixels, &TextureOptions::default())
+    }
+
+    /// RGBA texture of uninitialised pixels, e.g. to render into.
+    pub fn try_empty(width: u32, height: u32, options: &TextureOptions) -> Result<Self, CardlessError> {
+        Self::try_upload(gl::RGBA8, width, height, gl::RGBA, &[], options)
+    }
+
+    /// Single-channel `R8` texture, bottom row first, for masks and font
+    /// glyphs. It samples as white with the value as alpha, so sprites drawn
+    /// with it take their colour from the tint.
+    pub fn try_from_r8(width: u32, height: u32, pixels: &[u8]) -> Result<Self, CardlessError> {
+        check_pixels(width, height, 1, pixels)?;
+        let texture = Self::try_upload(gl::R8, width, height, gl::RED, pixels, &TextureOptions::default())?;
 
-        unsafe { gl::GenerateMipmap(gl::TEXTURE_2D); }
+        device::with(|device| {
+            device.texture_parameter(gl::TEXTURE_SWIZZLE_R, gl::ONE as i32);
+            device.texture_parameter(gl::TEXTURE_SWIZZLE_G, gl::ONE as i32);
+            device.texture_parameter(gl::TEXTURE_SWIZZLE_B, gl::ONE as i32);
+            device.texture_parameter(gl::TEXTURE_SWIZZLE_A, gl::RED as i32);
+        });
+        check_gl("texture swizzle")?;
 
-        Some(Self {handler})
+        Ok(texture)
+    }
+
+    /// Creates the texture and leaves it bound.
+    fn try_upload(internal_format: u32, width: u32, height: u32, format: u32, pixels: &[u8], options: &TextureOptions) -> Result<Self, CardlessError> {
+        let handler = device::with(|device| {
+            let handler = device.create_texture();
+            device.bind_texture(handler);
+            device.texture_image_2d(internal_format, width, height, format, pixels);
+            options.apply(device);
+            handler
+        });
+
+        let opaque = match format {
+            gl::RGB => true,
+            gl::RGBA => !pixels.is_empty() && opaque_rgba(pixels),
+            _ => false,
+        };
+
+        let texture = Self {handler, width, height, internal_format, opaque, mipmaps: options.mipmaps};
+        check_gl("texture upload")?;
+
+        Ok(texture)
+    }
+
+    /// States whether every pixel is fully opaque, instead of what the upload
+    /// found, e.g. for a texture only drawn from opaque areas.
+    pub fn with_opaque(mut self, opaque: bool) -> Self {
+        self.opaque = opaque;
+        self
+    }
+
+    /// Changes how the texture is sampled.
+    pub fn set_options(&mut self, options: &TextureOptions) -> Result<(), CardlessError> {
+        device::with(|device| {
+            device.bind_texture(self.handler);
+            options.apply(device);
+        });
+        self.mipmaps = options.mipmaps;
+        check_gl("texture options")
+    }
+
+    /// Replaces the `width` x `height` pixels whose bottom-left corner is at
+    /// `x`, `y`, bottom row first: 4 bytes per pixel for RGBA textures, 1 for R8.
+    pub fn update_region(&mut self, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) -> Result<(), CardlessError> {
+        let inside = x.checked_add(width).is_some_and(|right| right <= self.width)
+            && y.checked_add(height).is_some_and(|top| top <= self.height);
+        if !inside {
+            return Err(CardlessError::RegionOutOfBounds {
+                x, y, width, height,
+                texture_width: self.width,
+                texture_height: self.height,
+            });
+        }
+        let (format, bytes) = match self.internal_format {
+            gl::R8 => (gl::RED, 1),
+            _ => (gl::RGBA, 4),
+        };
+        check_pixels(width, height, bytes, pixels)?;
+        if format == gl::RGBA {
+            self.opaque = self.opaque && opaque_rgba(pixels);
+        }
+
+        device::with(|device| {
+            device.bind_texture(self.handler);
+            device.texture_sub_image_2d(x, y, width, height, format, pixels);
+            if self.mipmaps {
+                device.generate_mipmap();
+            }
+        });
+        check_gl("texture update")
     }
 
     pub fn bind(&self) {
-        unsafe { gl::BindTexture(gl::TEXTURE_2D, self.handler); }
+        device::with(|device| device.bind_texture(self.handler));
     }
 }
 
+/// Errors unless `pixels` holds exactly `width` x `height` pixels of `bytes`
+/// bytes each.
+fn check_pixels(width: u32, height: u32, bytes: usize, pixels: &[u8]) -> Result<(), CardlessError> {
+    let expected = width as usize * height as usize * bytes;
+    match pixels.len() {
+        actual if actual == expected => Ok(()),
+        actual => Err(CardlessError::InvalidPixelData { expected, actual }),
+    }
+}
+
+pub(super) fn opaque_rgba(pixels: &[u8]) -> bool {
+    pixels.chunks_exact(4).all(|pixel| pixel[3] == 255)
+}
+
 impl Drop for Texture {
     fn drop(&mut self) {
-        unsafe { gl::DeleteTextures(1, &self.handler); }
+        device::with(|device| device.delete_texture(self.handler));
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat};
+
+    use crate::cardless::device::{self, Command};
+
+    use crate::cardless::error::CardlessError;
+
+    use super::{Filter, Texture, TextureOptions, Wrap};
+
+    fn uploads(log: &[Command]) -> Vec<Command> {
+        log.iter().filter(|command| matches!(command, Command::TextureImage2D { .. })).cloned().collect()
+    }
+
+    #[test]
+    fn detects_formats_and_converts_the_rest() {
+        let log = device::record();
+
+        let mut bmp = Vec::new();
+        DynamicImage::new_rgb8(3, 2).write_to(&mut bmp, ImageOutputFormat::Bmp).unwrap();
+        Texture::try_load(Cursor::new(bmp)).unwrap();
+        let mut png = Vec::new();
+        DynamicImage::new_luma8(4, 1).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        Texture::try_load(Cursor::new(png)).unwrap();
+        Texture::try_from_image(&DynamicImage::new_rgba16(5, 5)).unwrap();
+
+        assert_eq!(uploads(&log.borrow()), vec![
+            Command::TextureImage2D { internal_format: gl::RGBA8, width: 3, height: 2, format: gl::RGB },
+            Command::TextureImage2D { internal_format: gl::RGBA8, width: 4, height: 1, format: gl::RGBA },
+            Command::TextureImage2D { internal_format: gl::RGBA8, width: 5, height: 5, format: gl::RGBA },
+        ]);
+    }
+
+    #[test]
+    fn r8_textures_sample_as_alpha() {
+        let log = device::record();
+        Texture::try_from_r8(2, 2, &[0, 64, 128, 255]).unwrap();
+
+        let log = log.borrow();
+        assert_eq!(uploads(&log), vec![Command::TextureImage2D { internal_format: gl::R8, width: 2, height: 2, format: gl::RED }]);
+        assert!(log.contains(&Command::TextureParameter { parameter: gl::TEXTURE_SWIZZLE_A, value: gl::RED as i32 }));
+    }
+
+    #[test]
+    fn rejects_wrongly_sized_pixel_data() {
+        let log = device::record();
+
+        assert!(matches!(Texture::try_from_rgba(2, 2, &[0; 15]), Err(CardlessError::InvalidPixelData { expected: 16, actual: 15 })));
+        assert!(matches!(Texture::try_from_r8(2, 1, &[0; 4]), Err(CardlessError::InvalidPixelData { expected: 2, actual: 4 })));
+        assert!(uploads(&log.borrow()).is_empty());
+    }
+
+    #[test]
+    fn options_set_sampling_state() {
+        let log = device::record();
+        let options = TextureOptions::pixel_art().with_wrap_st(Wrap::Mirror, Wrap::Border).with_border_color([1., 0., 0., 1.]).with_anisotropy(32.);
+        let mut texture = Texture::try_from_rgba(1, 1, &[0; 4]).unwrap();
+        texture.set_options(&options).unwrap();
+
+        let log = log.borrow();
+        let start = log.iter().rposition(|command| matches!(command, Command::BindTexture(_))).unwrap();
+        assert_eq!(log[start + 1..], [
+            Command::TextureParameter { parameter: gl::TEXTURE_WRAP_S, value: gl::MIRRORED_REPEAT as i32 },
+            Command::TextureParameter { parameter: gl::TEXTURE_WRAP_T, value: gl::CLAMP_TO_BORDER as i32 },
+            Command::TextureParameter { parameter: gl::TEXTURE_MIN_FILTER, value: gl::NEAREST as i32 },
+            Command::TextureParameter { parameter: gl::TEXTURE_MAG_FILTER, value: gl::NEAREST as i32 },
+            Command::TextureParameterF32 { parameter: gl::TEXTURE_BORDER_COLOR, values: vec![1., 0., 0., 1.] },
+            Command::TextureParameterF32 { parameter: device::TEXTURE_MAX_ANISOTROPY, values: vec![16.] },
+        ]);
+
+        assert_eq!(TextureOptions::new().with_filter(Filter::Linear).mag_filter, Filter::Linear);
+    }
+
+    #[test]
+    fn remembers_its_size_and_updates_regions() {
+        let log = device::record();
+        let mut texture = Texture::try_from_image(&DynamicImage::new_luma8(8, 4)).unwrap();
+        assert_eq!((texture.width, texture.height, texture.internal_format), (8, 4, gl::RGBA8));
+        assert!(texture.opaque);
+
+        texture.update_region(6, 1, 2, 3, &[0; 2 * 3 * 4]).unwrap();
+        assert!(!texture.opaque);
+        let mask = Texture::try_from_r8(4, 4, &[0; 16]).unwrap();
+        assert!(!mask.opaque);
+
+        let log = log.borrow();
+        let update = log.iter().position(|command| *command == Command::TextureSubImage2D { x: 6, y: 1, width: 2, height: 3, format: gl::RGBA }).unwrap();
+        assert_eq!(log[update + 1], Command::GenerateMipmap);
+        assert_eq!(mask.internal_format, gl::R8);
+    }
+
+    #[test]
+    fn rejects_regions_outside_the_texture() {
+        device::record();
+        let mut texture = Texture::try_from_rgba(2, 2, &[0; 16]).unwrap();
+
+        let error = texture.update_region(1, 1, 2, 1, &[0; 8]).unwrap_err();
+        assert!(matches!(error, CardlessError::RegionOutOfBounds { texture_width: 2, texture_height: 2, .. }));
+        assert_eq!(error.to_string(), "2x1 region at 1, 1 is outside the 2x2 texture");
+    }
+
+    #[test]
+    fn rejects_regions_wrapping_around() {
+        device::record();
+        let mut texture = Texture::try_from_rgba(2, 2, &[0; 16]).unwrap();
+
+        let result = texture.update_region(u32::MAX, 0, 2, 0, &[]);
+        assert!(matches!(result, Err(CardlessError::RegionOutOfBounds { x: u32::MAX, .. })));
+    }
+
+    #[test]
+    fn rejects_regions_with_the_wrong_pixel_count() {
+        let log = device::record();
+        let mut texture = Texture::try_from_r8(2, 2, &[0; 4]).unwrap();
+
+        let result = texture.update_region(0, 0, 2, 1, &[0; 8]);
+        assert!(matches!(result, Err(CardlessError::InvalidPixelData { expected: 2, actual: 8 })));
+        assert!(!log.borrow().iter().any(|command| matches!(command, Command::TextureSubImage2D { .. })));
+    }
+}
@@ -0,0 +1,245 @@
+use std::slice;
+
+use super::device::RenderDevice;
+
+/// Uniform of a linked program as reported by the driver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveUniform {
+    pub name: String,
+    /// GLSL type, e.g. `gl::FLOAT_VEC3`.
+    pub gl_type: u32,
+    /// Number of array elements, 1 for plain uniforms. Drivers may leave out
+    /// elements past the last one the shader reads.
+    pub size: usize,
+    /// `None` for uniforms living in a uniform block.
+    pub location: Option<i32>,
+}
+
+/// Value that [`ShaderProgram::set_uniform`](super::shader_program::ShaderProgram::set_uniform)
+/// accepts: a single [`UniformElement`] or a slice, array or `Vec` of them.
+pub trait Uniform {
+    /// GLSL type the uniform has to be declared with.
+    fn gl_type(&self) -> u32;
+    fn upload(&self, location: i32, device: &mut dyn RenderDevice);
+}
+
+/// Type of a single uniform value, which can also be uploaded as an array.
+pub trait UniformElement: Sized {
+    const GL_TYPE: u32;
+    fn upload_slice(values: &[Self], location: i32, device: &mut dyn RenderDevice);
+}
+
+impl<T: UniformElement> Uniform for T {
+    fn gl_type(&self) -> u32 {
+        T::GL_TYPE
+    }
+
+    fn upload(&self, location: i32, device: &mut dyn RenderDevice) {
+        T::upload_slice(slice::from_ref(self), location, device);
+    }
+}
+
+impl<T: UniformElement> Uniform for [T] {
+    fn gl_type(&self) -> u32 {
+        T::GL_TYPE
+    }
+
+    fn upload(&self, location: i32, device: &mut dyn RenderDevice) {
+        T::upload_slice(self, location, device);
+    }
+}
+
+impl<T: UniformElement, const N: usize> Uniform for [T; N] {
+    fn gl_type(&self) -> u32 {
+        T::GL_TYPE
+    }
+
+    fn upload(&self, location: i32, device: &mut dyn RenderDevice) {
+        T::upload_slice(self, location, device);
+    }
+}
+
+impl<T: UniformElement> Uniform for Vec<T> {
+    fn gl_type(&self) -> u32 {
+        T::GL_TYPE
+    }
+
+    fn upload(&self, location: i32, device: &mut dyn RenderDevice) {
+        T::upload_slice(self, location, device);
+    }
+}
+
+/// Reinterprets `#[repr(C)]` values made of `components` scalars each.
+fn scalars<T, S>(values: &[T], components: usize) -> &[S] {
+    unsafe { slice::from_raw_parts(values.as_ptr() as *const S, values.len() * components) }
+}
+
+// $size is what the device call takes: components for vectors, dimension for
+// matrices. $scalars is the number of scalars in one value.
+macro_rules! uniform_element {
+    ($($t:ty, $gl_type:expr, $upload:ident, $size:expr, $scalars:expr, $scalar:ty;)+) => {
+        $(
+            impl UniformElement for $t {
+                const GL_TYPE: u32 = $gl_type;
+
+                fn upload_slice(values: &[Self], location: i32, device: &mut dyn RenderDevice) {
+                    device.$upload(location, $size, scalars::<Self, $scalar>(values, $scalars));
+                }
+            }
+        )+
+    };
+}
+
+uniform_element! {
+    f32, gl::FLOAT, uniform_f32, 1, 1, f32;
+    glm::Vec2, gl::FLOAT_VEC2, uniform_f32, 2, 2, f32;
+    glm::Vec3, gl::FLOAT_VEC3, uniform_f32, 3, 3, f32;
+    glm::Vec4, gl::FLOAT_VEC4, uniform_f32, 4, 4, f32;
+    i32, gl::INT, uniform_i32, 1, 1, i32;
+    glm::IVec2, gl::INT_VEC2, uniform_i32, 2, 2, i32;
+    glm::IVec3, gl::INT_VEC3, uniform_i32, 3, 3, i32;
+    glm::IVec4, gl::INT_VEC4, uniform_i32, 4, 4, i32;
+    u32, gl::UNSIGNED_INT, uniform_u32, 1, 1, u32;
+    glm::UVec2, gl::UNSIGNED_INT_VEC2, uniform_u32, 2, 2, u32;
+    glm::UVec3, gl::UNSIGNED_INT_VEC3, uniform_u32, 3, 3, u32;
+    glm::UVec4, gl::UNSIGNED_INT_VEC4, uniform_u32, 4, 4, u32;
+    glm::Mat2, gl::FLOAT_MAT2, uniform_matrix, 2, 4, f32;
+    glm::Mat3, gl::FLOAT_MAT3, uniform_matrix, 3, 9, f32;
+    glm::Mat4, gl::FLOAT_MAT4, uniform_matrix, 4, 16, f32;
+}
+
+// Booleans are uploaded as integers, one per component.
+macro_rules! bool_uniform_element {
+    ($($t:ty, $gl_type:expr, $components:expr;)+) => {
+        $(
+            impl UniformElement for $t {
+                const GL_TYPE: u32 = $gl_type;
+
+                fn upload_slice(values: &[Self], location: i32, device: &mut dyn RenderDevice) {
+                    let ints: Vec<i32> = scalars::<Self, bool>(values, $components).iter().map(|&b| b as i32).collect();
+                    device.uniform_i32(location, $components, &ints);
+                }
+            }
+        )+
+    };
+}
+
+bool_uniform_element! {
+    bool, gl::BOOL, 1;
+    glm::BVec2, gl::BOOL_VEC2, 2;
+    glm::BVec3, gl::BOOL_VEC3, 3;
+    glm::BVec4, gl::BOOL_VEC4, 4;
+}
+
+/// Explains why setting `value` on the uniform `name` does nothing useful,
+/// `location` and `active` being what the program knows about that name.
+pub fn check<T: Uniform + ?Sized>(name: &str, location: Option<i32>, active: Option<&ActiveUniform>, value: &T) -> Option<String> {
+    if location.is_none() {
+        return Some(format!("uniform `{}` does not exist or is not used by the shader", name));
+    }
+
+    let active = active?;
+    let matches = active.gl_type == value.gl_type()
+        // Samplers are set to the texture unit they read from.
+        || (is_sampler(active.gl_type) && value.gl_type() == gl::INT);
+    if !matches {
+        return Some(format!("uniform `{}` is declared as {} but was set to {}", name, gl_type_name(active.gl_type), gl_type_name(value.gl_type())));
+    }
+
+    None
+}
+
+fn is_sampler(gl_type: u32) -> bool {
+    matches!(gl_type,
+        gl::SAMPLER_1D | gl::SAMPLER_2D | gl::SAMPLER_3D | gl::SAMPLER_CUBE
+        | gl::SAMPLER_1D_ARRAY | gl::SAMPLER_2D_ARRAY | gl::SAMPLER_2D_RECT | gl::SAMPLER_BUFFER
+        | gl::SAMPLER_1D_SHADOW | gl::SAMPLER_2D_SHADOW | gl::SAMPLER_CUBE_SHADOW
+        | gl::SAMPLER_1D_ARRAY_SHADOW | gl::SAMPLER_2D_ARRAY_SHADOW | gl::SAMPLER_2D_RECT_SHADOW
+        | gl::SAMPLER_2D_MULTISAMPLE | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+        | gl::INT_SAMPLER_1D | gl::INT_SAMPLER_2D | gl::INT_SAMPLER_3D | gl::INT_SAMPLER_CUBE
+        | gl::INT_SAMPLER_1D_ARRAY | gl::INT_SAMPLER_2D_ARRAY | gl::INT_SAMPLER_2D_RECT | gl::INT_SAMPLER_BUFFER
+        | gl::UNSIGNED_INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_2D | gl::UNSIGNED_INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_CUBE
+        | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_RECT | gl::UNSIGNED_INT_SAMPLER_BUFFER)
+}
+
+/// GLSL spelling of a type, e.g. `vec3` for `gl::FLOAT_VEC3`.
+pub fn gl_type_name(gl_type: u32) -> String {
+    let name = match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl_type if is_sampler(gl_type) => "sampler",
+        gl_type => return format!("GL type 0x{:x}", gl_type),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cardless::device::{Command, RecordingDevice};
+
+    use super::{check, ActiveUniform, Uniform};
+
+    fn active(gl_type: u32, size: usize) -> ActiveUniform {
+        ActiveUniform { name: "u_value".to_string(), gl_type, size, location: Some(0) }
+    }
+
+    #[test]
+    fn uploads_matrices_and_booleans() {
+        let mut device = RecordingDevice::new();
+        let log = device.log();
+
+        [glm::Mat4::new(glm::vec4(1., 0., 0., 0.), glm::vec4(0., 1., 0., 0.), glm::vec4(0., 0., 1., 0.), glm::vec4(2., 3., 4., 1.)); 2]
+            .upload(0, &mut device);
+        glm::bvec3(true, false, true).upload(1, &mut device);
+
+        let log = log.borrow();
+        match &log[0] {
+            Command::UniformMatrix { location: 0, dimension: 4, v } => {
+                assert_eq!(v.len(), 32);
+                assert_eq!(&v[12..16], &[2., 3., 4., 1.]);
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+        assert_eq!(log[1], Command::UniformI32 { location: 1, components: 3, v: vec![1, 0, 1] });
+    }
+
+    #[test]
+    fn reports_wrong_uses() {
+        assert!(check("u_value", Some(0), Some(&active(gl::FLOAT_VEC3, 1)), &glm::vec3(0., 0., 0.)).is_none());
+        assert!(check("u_value", Some(0), Some(&active(gl::SAMPLER_2D, 4)), &[0, 1, 2, 3]).is_none());
+
+        assert_eq!(
+            check("u_value", None, None, &1.0f32).unwrap(),
+            "uniform `u_value` does not exist or is not used by the shader");
+        assert_eq!(
+            check("u_value", Some(0), Some(&active(gl::FLOAT_VEC3, 1)), &glm::vec4(0., 0., 0., 0.)).unwrap(),
+            "uniform `u_value` is declared as vec3 but was set to vec4");
+    }
+}
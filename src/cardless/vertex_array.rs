@@ -0,0 +1,85 @@
+use super::{buffer::Buffer, device::{self, RenderDevice}, error::{CardlessError, check_gl}, vertex_attribute::{self, Vertex, VertexAttribute}};
+
+/// Vertex array object remembering which buffers a draw reads from and how
+/// the vertices of `T` are laid out in them.
+pub struct VertexArray {
+    pub handler: u32,
+    elements: u32,
+}
+
+impl VertexArray {
+    pub fn try_new<T>(vertices: &Buffer<T>, elements: &Buffer<u32>) -> Result<Self, CardlessError>
+    where T: Vertex {
+        let layout = T::get_attributes_layout();
+        vertex_attribute::validate_layout(&layout)?;
+
+        let handler = device::with(|device| {
+            let handler = device.create_vertex_array();
+            device.bind_vertex_array(handler);
+
+            attach(device, vertices.handler, &layout, 0);
+            device.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, elements.handler);
+
+            device.bind_vertex_array(0);
+            handler
+        });
+
+        let vertex_array = Self { handler, elements: elements.handler };
+        check_gl("vertex array creation")?;
+
+        Ok(vertex_array)
+    }
+
+    /// Like [`Self::try_new`], with the attributes of `I` read from `instances`
+    /// once per instance. Their locations must not overlap the ones of `T`.
+    pub fn try_new_instanced<T, I>(vertices: &Buffer<T>, instances: &Buffer<I>, elements: &Buffer<u32>) -> Result<Self, CardlessError>
+    where T: Vertex, I: Vertex {
+        let layout = T::get_attributes_layout();
+        let instance_layout = I::get_attributes_layout();
+        vertex_attribute::validate_layout(&layout)?;
+        vertex_attribute::validate_layout(&instance_layout)?;
+
+        let handler = device::with(|device| {
+            let handler = device.create_vertex_array();
+            device.bind_vertex_array(handler);
+
+            attach(device, vertices.handler, &layout, 0);
+            attach(device, instances.handler, &instance_layout, 1);
+            device.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, elements.handler);
+
+            device.bind_vertex_array(0);
+            handler
+        });
+
+        let vertex_array = Self { handler, elements: elements.handler };
+        check_gl("vertex array creation")?;
+
+        Ok(vertex_array)
+    }
+
+    /// Binds the vertex array along with its element buffer, which creating
+    /// another element buffer while it was bound would have replaced.
+    pub fn bind(&self) {
+        device::with(|device| {
+            device.bind_vertex_array(self.handler);
+            device.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, self.elements);
+        });
+    }
+}
+
+fn attach(device: &mut dyn RenderDevice, buffer: u32, layout: &[VertexAttribute], divisor: u32) {
+    device.bind_buffer(gl::ARRAY_BUFFER, buffer);
+    for (location, attribute) in vertex_attribute::locations(layout) {
+        device.vertex_attribute_pointer(location, attribute);
+        device.enable_vertex_attribute(location);
+        if divisor != 0 {
+            device.vertex_attribute_divisor(location, divisor);
+        }
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        device::with(|device| device.delete_vertex_array(self.handler));
+    }
+}
@@ -1,23 +1,263 @@
+use super::{error::CardlessError, uniform::gl_type_name};
+
+pub use cardless_derive::Vertex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VertexAttributeType {
     F32,
     I32,
     U32,
+    U8,
+    I8,
+    U16,
+    I16,
+    F16,
+    /// Four components packed in a `u32` as 10, 10, 10 and 2 signed bits,
+    /// x in the lowest ones. Always read as floats, so `size` must be 4.
+    I2_10_10_10,
+    /// Unsigned version of [`Self::I2_10_10_10`].
+    U2_10_10_10,
+}
+
+impl VertexAttributeType {
+    /// Value passed to `glVertexAttribPointer`, e.g. `gl::UNSIGNED_BYTE`.
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            VertexAttributeType::F32 => gl::FLOAT,
+            VertexAttributeType::I32 => gl::INT,
+            VertexAttributeType::U32 => gl::UNSIGNED_INT,
+            VertexAttributeType::U8 => gl::UNSIGNED_BYTE,
+            VertexAttributeType::I8 => gl::BYTE,
+            VertexAttributeType::U16 => gl::UNSIGNED_SHORT,
+            VertexAttributeType::I16 => gl::SHORT,
+            VertexAttributeType::F16 => gl::HALF_FLOAT,
+            VertexAttributeType::I2_10_10_10 => gl::INT_2_10_10_10_REV,
+            VertexAttributeType::U2_10_10_10 => gl::UNSIGNED_INT_2_10_10_10_REV,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, VertexAttributeType::F32 | VertexAttributeType::F16)
+    }
+
+    pub fn is_packed(&self) -> bool {
+        matches!(self, VertexAttributeType::I2_10_10_10 | VertexAttributeType::U2_10_10_10)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, VertexAttributeType::I32 | VertexAttributeType::I8 | VertexAttributeType::I16 | VertexAttributeType::I2_10_10_10)
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct VertexAttribute {
     pub attribute_type: VertexAttributeType,
     pub size: usize,
     pub normalized: bool,
     pub stride: usize,
     pub width: usize,
+    /// Shader input location, the index in the layout when `None`.
+    pub location: Option<u32>,
 }
 
 impl VertexAttribute {
     pub fn new(attribute_type: VertexAttributeType, size: usize, normalized: bool, stride: usize, width: usize) -> Self {
-        Self { attribute_type, size, normalized, stride, width }
+        Self { attribute_type, size, normalized, stride, width, location: None }
+    }
+
+    pub fn with_location(mut self, location: u32) -> Self {
+        self.location = Some(location);
+        self
     }
 }
 
+/// Pairs every attribute of `layout` with its shader input location.
+pub fn locations(layout: &[VertexAttribute]) -> impl Iterator<Item = (u32, &VertexAttribute)> {
+    layout.iter().enumerate().map(|(i, attribute)| (attribute.location.unwrap_or(i as u32), attribute))
+}
+
 pub trait Vertex: Sized {
     fn get_attributes_layout() -> Vec<VertexAttribute>;
 }
+
+/// Vertex input of a linked program as reported by the driver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveAttribute {
+    pub name: String,
+    /// GLSL type, e.g. `gl::FLOAT_VEC2`.
+    pub gl_type: u32,
+    pub size: usize,
+    /// `None` for built-ins such as `gl_VertexID`.
+    pub location: Option<u32>,
+}
+
+impl VertexAttribute {
+    /// Whether the shader reads the attribute as integers, through
+    /// `glVertexAttribIPointer`, otherwise as floats.
+    pub fn is_integer(&self) -> bool {
+        !self.attribute_type.is_float() && !self.attribute_type.is_packed() && !self.normalized
+    }
+
+    /// GLSL type of the shader input this attribute feeds, e.g. `gl::INT_VEC2`.
+    pub fn gl_type(&self) -> u32 {
+        let types = match self.attribute_type {
+            _ if !self.is_integer() => [gl::FLOAT, gl::FLOAT_VEC2, gl::FLOAT_VEC3, gl::FLOAT_VEC4],
+            attribute_type if attribute_type.is_signed() => [gl::INT, gl::INT_VEC2, gl::INT_VEC3, gl::INT_VEC4],
+            _ => [gl::UNSIGNED_INT, gl::UNSIGNED_INT_VEC2, gl::UNSIGNED_INT_VEC3, gl::UNSIGNED_INT_VEC4],
+        };
+        types[self.size.clamp(1, 4) - 1]
+    }
+
+    /// Describes what OpenGL would reject or silently misread in this attribute.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=4).contains(&self.size) {
+            return Err(format!("has {} components, attributes have 1 to 4", self.size));
+        }
+        if self.attribute_type.is_packed() && self.size != 4 {
+            return Err(format!("packs 4 components but has size {}", self.size));
+        }
+        if self.normalized && self.attribute_type.is_float() {
+            return Err("is normalized but only integers can be".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks every attribute of `layout` with [`VertexAttribute::validate`].
+pub fn validate_layout(layout: &[VertexAttribute]) -> Result<(), CardlessError> {
+    for (location, attribute) in locations(layout) {
+        attribute.validate().map_err(|message| CardlessError::Layout { location, message })?;
+    }
+    Ok(())
+}
+
+/// Checks that the layout is valid and that every input of the vertex shader
+/// is fed by the attribute of `layout` at its location, with the same type
+/// and component count. Layout entries the shader does not read are fine.
+pub fn check_layout(attributes: &[ActiveAttribute], layout: &[VertexAttribute]) -> Result<(), CardlessError> {
+    validate_layout(layout)?;
+
+    for attribute in attributes {
+        let location = match attribute.location {
+            Some(location) => location,
+            None => continue,
+        };
+        let mismatch = |message: String| CardlessError::AttributeMismatch {
+            name: attribute.name.clone(),
+            location,
+            message,
+        };
+
+        let provided = match locations(layout).find(|(provided, _)| *provided == location) {
+            Some((_, provided)) => provided,
+            None => return Err(mismatch("has no entry in the vertex layout".to_string())),
+        };
+        if provided.gl_type() != attribute.gl_type {
+            return Err(mismatch(format!(
+                "is declared as {} but the vertex layout provides {}",
+                gl_type_name(attribute.gl_type),
+                gl_type_name(provided.gl_type()))));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cardless::error::CardlessError;
+
+    use super::{check_layout, validate_layout, ActiveAttribute, Vertex, VertexAttribute, VertexAttributeType};
+
+    fn attribute(name: &str, gl_type: u32, location: Option<u32>) -> ActiveAttribute {
+        ActiveAttribute { name: name.to_string(), gl_type, size: 1, location }
+    }
+
+    fn layout() -> Vec<VertexAttribute> {
+        vec![
+            VertexAttribute::new(VertexAttributeType::F32, 2, false, 20, 0),
+            VertexAttribute::new(VertexAttributeType::I32, 1, false, 20, 8),
+        ]
+    }
+
+    fn message(result: Result<(), CardlessError>) -> String {
+        match result {
+            Err(CardlessError::AttributeMismatch { message, .. }) => message,
+            _ => panic!("expected an attribute mismatch"),
+        }
+    }
+
+    #[test]
+    fn accepts_matching_inputs() {
+        let attributes = [
+            attribute("vert_texture", gl::INT, Some(1)),
+            attribute("gl_VertexID", gl::INT, None),
+        ];
+
+        assert!(check_layout(&attributes, &layout()).is_ok());
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let components = [attribute("vert_pos", gl::FLOAT_VEC3, Some(0))];
+        assert_eq!(message(check_layout(&components, &layout())), "is declared as vec3 but the vertex layout provides vec2");
+
+        let base_type = [attribute("vert_texture", gl::FLOAT, Some(1))];
+        assert_eq!(message(check_layout(&base_type, &layout())), "is declared as float but the vertex layout provides int");
+
+        let location = [attribute("vert_color", gl::FLOAT_VEC4, Some(2))];
+        assert_eq!(message(check_layout(&location, &layout())), "has no entry in the vertex layout");
+    }
+
+    #[test]
+    fn picks_the_shader_input_type() {
+        let attribute = |attribute_type, size, normalized| VertexAttribute::new(attribute_type, size, normalized, 16, 0).gl_type();
+
+        assert_eq!(attribute(VertexAttributeType::U8, 4, true), gl::FLOAT_VEC4);
+        assert_eq!(attribute(VertexAttributeType::U16, 2, false), gl::UNSIGNED_INT_VEC2);
+        assert_eq!(attribute(VertexAttributeType::I8, 3, false), gl::INT_VEC3);
+        assert_eq!(attribute(VertexAttributeType::I16, 2, true), gl::FLOAT_VEC2);
+        assert_eq!(attribute(VertexAttributeType::F16, 2, false), gl::FLOAT_VEC2);
+        assert_eq!(attribute(VertexAttributeType::I2_10_10_10, 4, false), gl::FLOAT_VEC4);
+    }
+
+    #[test]
+    fn rejects_invalid_attributes() {
+        let layout = |attribute_type, size, normalized| vec![VertexAttribute::new(attribute_type, size, normalized, 16, 0)];
+        let message = |result| match result {
+            Err(CardlessError::Layout { message, .. }) => message,
+            _ => panic!("expected a layout error"),
+        };
+
+        assert!(validate_layout(&layout(VertexAttributeType::U2_10_10_10, 4, true)).is_ok());
+        assert_eq!(message(validate_layout(&layout(VertexAttributeType::U2_10_10_10, 3, true))), "packs 4 components but has size 3");
+        assert_eq!(message(validate_layout(&layout(VertexAttributeType::F16, 2, true))), "is normalized but only integers can be");
+        assert_eq!(message(validate_layout(&layout(VertexAttributeType::U8, 5, true))), "has 5 components, attributes have 1 to 4");
+    }
+
+    #[derive(Vertex)]
+    #[repr(C)]
+    struct ColorVertex {
+        pos: glm::Vec3,
+        #[vertex(location = 4)]
+        color: [u8; 4],
+        #[vertex(normalized)]
+        weight: u32,
+        slots: glm::IVec2,
+    }
+
+    #[test]
+    fn derives_layout_from_fields() {
+        let stride = std::mem::size_of::<ColorVertex>();
+        let expected = vec![
+            VertexAttribute::new(VertexAttributeType::F32, 3, false, stride, 0).with_location(0),
+            VertexAttribute::new(VertexAttributeType::U8, 4, true, stride, 12).with_location(4),
+            VertexAttribute::new(VertexAttributeType::U32, 1, true, stride, 16).with_location(5),
+            VertexAttribute::new(VertexAttributeType::I32, 2, false, stride, 20).with_location(6),
+        ];
+
+        assert_eq!(ColorVertex::get_attributes_layout(), expected);
+        assert_eq!(expected[1].gl_type(), gl::FLOAT_VEC4);
+        assert_eq!(expected[3].gl_type(), gl::INT_VEC2);
+    }
+}
@@ -0,0 +1,4 @@
+// Lets `#[derive(Vertex)]`, which names this crate, be used inside it.
+extern crate self as cardless_game_engine;
+
+pub mod cardless;
@@ -1,104 +1,114 @@
 extern crate glfw;
 use std::{io::BufReader, fs::File, time::Duration};
 
-use cardless::simple2d_renderer::BatchRenderer;
+use cardless_game_engine::cardless::{camera::Camera2D, device, error::CardlessError, resolution::{Scaling, VirtualResolution, VirtualScreen}, simple2d_renderer::BatchRenderer, texture::Texture};
 use glm::vec2;
 
-use crate::cardless::texture::Texture;
-
 use self::glfw::Context;
 
-
-mod cardless;
 extern crate gl;
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS)
-        .expect("Failed to initialize glfw");
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+const VERTEX_SHADER: &str = "./shaders/simple2d.vert";
+const FRAGMENT_SHADER: &str = "./shaders/simple2d.frag";
 
-    let (mut window, _events) = glfw.create_window(800, 600, "Hello world", glfw::WindowMode::Windowed)
-        .expect("Failed to create window");
+/// Resolution the scene is drawn at, letterboxed into the window.
+const LOGICAL_WIDTH: u32 = 800;
+const LOGICAL_HEIGHT: u32 = 600;
 
-    window.make_current();
-    window.set_key_polling(true);
-    window.set_framebuffer_size_polling(true);
+fn setup_gl_state() {
+    device::with(|device| {
+        device.cull(true);
+        device.blend(true);
+    });
+}
 
-    gl::load_with(|symbol| window.get_proc_address(symbol));
+fn load_textures() -> Result<[Texture; 3], CardlessError> {
+    let image_a = File::open("./sample_texture_0.png")?;
+    let image_a = Texture::try_load(BufReader::new(image_a))?;
+    let image_b = File::open("./sample_texture_1.png")?;
+    let image_b = Texture::try_load(BufReader::new(image_b))?;
+    let image_c = File::open("./sample_texture_2.png")?;
+    let image_c = Texture::try_load(BufReader::new(image_c))?;
+
+    Ok([image_a, image_b, image_c])
+}
+
+/// Camera over a `width` x `height` window, showing the scene from -1 to 1
+/// vertically and as far horizontally as the window is wide.
+fn scene_camera(width: i32, height: i32) -> Camera2D {
+    Camera2D::new(vec2(width as f32, height as f32)).with_zoom(height as f32 / 2.)
+}
+
+fn draw_scene(br: &mut BatchRenderer, textures: &[Texture; 3], time: f64) {
+    let [image_a, image_b, image_c] = textures;
+
+    device::with(|device| device.clear([0.6, 0.2, 0.6, 1.]));
+
+    br.push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), image_a);
+    br.push_square_texture(vec2(0.4, time.sin() as f32), vec2(0.2, 0.2), image_b);
+    br.push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), image_a);
+    br.push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), image_b);
+    br.push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), image_c);
+
+    br.flush();
+}
 
-    let mut vao = 0;
-    unsafe { gl::GenVertexArrays(1, &mut vao); }
-    unsafe { gl::BindVertexArray(vao); }
+/// Renders a single frame of the scene without opening a window and saves it
+/// to `path`, e.g. `cargo run --features headless -- --headless frame.png`.
+#[cfg(feature = "headless")]
+fn run_headless(path: &str) -> Result<(), CardlessError> {
+    let context = cardless_game_engine::cardless::headless::HeadlessContext::try_new(800, 600)?;
 
+    setup_gl_state();
 
+    let mut br = BatchRenderer::try_from_files(FRAGMENT_SHADER, VERTEX_SHADER)?;
+    let textures = load_textures()?;
 
-    unsafe { gl::Enable(gl::CULL_FACE); }
-    unsafe { gl::CullFace(gl::BACK); }
+    br.bind();
+    br.set_camera(&scene_camera(800, 600));
+    draw_scene(&mut br, &textures, 0.);
 
-    let vertex_shader = "
-#version 330 core
-layout (location = 0) in vec2 vert_pos;
-layout (location = 1) in vec2 vert_uv;
-layout (location = 2) in int vert_texture;
+    context.read_frame().save(path)?;
 
-out vec2 frag_uv;
-flat out int frag_texture;
+    Ok(())
+}
 
-void main() {
-    frag_uv = vert_uv;
-    frag_texture = vert_texture;
-    gl_Position = vec4(vert_pos.xy, 0.0, 1.0);
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
-        ";
-
-    let fragment_shader = "
-#version 330 core
-in vec2 frag_uv;
-flat in int frag_texture;
-
-out vec4 finale_color;
-
-uniform sampler2D u_texture[16];
-
-void main() {
-    vec4 v_color = vec4(0.4, 0.6, 0.2, 1);
-    switch(frag_texture) {
-        case 0: v_color = texture(u_texture[0], frag_uv); break;
-        case 1: v_color = texture(u_texture[1], frag_uv); break;
-        case 2: v_color = texture(u_texture[2], frag_uv); break;
-        case 3: v_color = texture(u_texture[3], frag_uv); break;
-        case 4: v_color = texture(u_texture[4], frag_uv); break;
-        case 5: v_color = texture(u_texture[5], frag_uv); break;
-        case 6: v_color = texture(u_texture[6], frag_uv); break;
-        case 7: v_color = texture(u_texture[7], frag_uv); break;
-        case 8: v_color = texture(u_texture[8], frag_uv); break;
-        case 9: v_color = texture(u_texture[9], frag_uv); break;
-        case 10: v_color = texture(u_texture[10], frag_uv); break;
-        case 11: v_color = texture(u_texture[11], frag_uv); break;
-        case 12: v_color = texture(u_texture[12], frag_uv); break;
-        case 13: v_color = texture(u_texture[13], frag_uv); break;
-        case 14: v_color = texture(u_texture[14], frag_uv); break;
-        case 15: v_color = texture(u_texture[15], frag_uv); break;
+
+fn run() -> Result<(), CardlessError> {
+    #[cfg(feature = "headless")]
+    {
+        let args: Vec<String> = std::env::args().collect();
+        if let [_, flag, path] = args.as_slice() {
+            if flag == "--headless" {
+                return run_headless(path);
+            }
+        }
     }
 
-    finale_color = v_color;
-    // finale_color = vec4(vec3(frag_uv, .0) / (frag_texture + 1), 1);
-    // finale_color = vec4(vec3((frag_texture + 1) / 3.0), 1.0);
-} 
-    ";
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS)
+        .expect("Failed to initialize glfw");
+    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+
+    let (mut window, _events) = glfw.create_window(800, 600, "Hello world", glfw::WindowMode::Windowed)
+        .expect("Failed to create window");
 
-    let mut br = BatchRenderer::new(fragment_shader, vertex_shader);
-    let image_a = File::open("./sample_texture_0.png").unwrap();
-    let image_a = Texture::try_load(BufReader::new(image_a)).unwrap();
-    let image_b = File::open("./sample_texture_1.png").unwrap();
-    let image_b = Texture::try_load(BufReader::new(image_b)).unwrap();
-    let image_c = File::open("./sample_texture_2.png").unwrap();
-    let image_c = Texture::try_load(BufReader::new(image_c)).unwrap();
+    window.make_current();
+    window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
 
+    gl::load_with(|symbol| window.get_proc_address(symbol));
 
-    unsafe { gl::Enable(gl::BLEND); }
-    unsafe { gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA); }
+    setup_gl_state();
+
+    let mut br = BatchRenderer::try_from_files(FRAGMENT_SHADER, VERTEX_SHADER)?;
+    let textures = load_textures()?;
 
     // 0 stands for TEXTURE0, so it applies image_a
     // 1 stands for TEXTURE1, therefore it applies image_b
@@ -107,26 +117,23 @@ void main() {
     let mut time_last_update = glfw.get_time();
 
 
+    let screen = VirtualScreen::try_new(VirtualResolution::new(LOGICAL_WIDTH, LOGICAL_HEIGHT, Scaling::Letterbox))?;
+
     br.bind();
+    br.set_camera(&scene_camera(LOGICAL_WIDTH as i32, LOGICAL_HEIGHT as i32));
     while !window.should_close() {
         let time_now = glfw.get_time();
         let time_delta = glfw.get_time() - time_last_update;
         time_last_update = time_now;
 
-        let (width, height) = window.get_size();
-        unsafe { gl::Viewport(0, 0, width, height); }
-
+        if let Err(e) = br.reload_shaders() {
+            eprintln!("{}", e);
+        }
 
-        unsafe { gl::ClearColor(0.6, 0.2, 0.6, 1.); }
-        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT); }
+        screen.render(|| draw_scene(&mut br, &textures, time_now));
 
-        br.push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), &image_a);
-        br.push_square_texture(vec2(0.4, time_now.sin() as f32), vec2(0.2, 0.2), &image_b);
-        br.push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), &image_a);
-        br.push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), &image_b);
-        br.push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), &image_c);
-
-        br.flush();
+        let (width, height) = window.get_framebuffer_size();
+        screen.present(width as u32, height as u32);
 
         window.swap_buffers();
         glfw.poll_events();
@@ -134,4 +141,6 @@ void main() {
         // 60 fps max
         std::thread::sleep(Duration::from_millis(1000 / 60));
     }
+
+    Ok(())
 }
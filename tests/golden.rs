@@ -0,0 +1,444 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Mutex};
+
+use cardless_game_engine::cardless::{atlas::AtlasBuilder, buffer::StreamingStrategy, camera::Camera2D, device, bundle::{Bundle, PackedBundle, BUNDLE_TEXTURE_SLOTS}, framebuffer::Framebuffer, shader::Preprocessor, golden::Golden, headless::HeadlessContext, instanced_renderer::InstancedRenderer, post_process::{identity_lut, PostEffect, PostProcessor}, resolution::{Scaling, VirtualResolution, VirtualScreen}, simple2d_renderer::BatchRenderer, sprite::{Rect, Sprite}, texture::{Filter, Texture, TextureOptions, Wrap}};
+use glm::vec2;
+
+const VERTEX_SHADER: &str = include_str!("../shaders/simple2d.vert");
+const FRAGMENT_SHADER: &str = include_str!("../shaders/simple2d.frag");
+const INSTANCED_VERTEX_SHADER: &str = include_str!("../shaders/instanced.vert");
+const INSTANCED_FRAGMENT_SHADER: &str = include_str!("../shaders/instanced.frag");
+
+// Only one headless context is alive at a time.
+static CONTEXT: Mutex<()> = Mutex::new(());
+
+fn load_texture(index: usize) -> Texture {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("sample_texture_{}.png", index));
+    Texture::try_load(BufReader::new(File::open(path).unwrap())).unwrap()
+}
+
+fn render<F>(name: &str, draw: F)
+where F: FnOnce(&mut BatchRenderer) {
+    render_many(name, 1, |renderers| draw(&mut renderers[0]));
+}
+
+/// Like [`render`], with `count` renderers sharing the frame.
+fn render_many<F>(name: &str, count: usize, draw: F)
+where F: FnOnce(&mut [BatchRenderer]) {
+    check_frame(name, || {
+        let mut renderers: Vec<BatchRenderer> = (0..count).map(|_| {
+            let mut br = BatchRenderer::try_new(FRAGMENT_SHADER, VERTEX_SHADER).unwrap();
+            br.bind();
+            br
+        }).collect();
+        draw(&mut renderers);
+    });
+}
+
+fn render_instanced<F>(name: &str, draw: F)
+where F: FnOnce(&mut InstancedRenderer) {
+    check_frame(name, || {
+        let mut ir = InstancedRenderer::try_new(INSTANCED_FRAGMENT_SHADER, INSTANCED_VERTEX_SHADER).unwrap();
+        ir.bind();
+        draw(&mut ir);
+    });
+}
+
+/// Runs `draw` on a fresh 200x150 frame and compares the result with the
+/// reference image `name`.
+fn check_frame<F>(name: &str, draw: F)
+where F: FnOnce() {
+    let _lock = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    let context = HeadlessContext::try_new(200, 150).unwrap();
+
+    device::with(|device| {
+        device.blend(true);
+        device.clear([0.6, 0.2, 0.6, 1.]);
+    });
+
+    draw();
+
+    let frame = context.read_frame();
+
+    let golden = Golden::new(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden"),
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden"),
+    );
+    if let Err(e) = golden.check(name, &frame) {
+        panic!("{}", e);
+    }
+}
+
+#[test]
+fn sample_textures() {
+    render("sample_textures", |br| {
+        let texture_a = load_texture(0);
+        let texture_b = load_texture(1);
+        let texture_c = load_texture(2);
+
+        br.push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), &texture_a);
+        br.push_square_texture(vec2(0.4, 0.0), vec2(0.2, 0.2), &texture_b);
+        br.push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), &texture_a);
+        br.push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), &texture_b);
+        br.push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), &texture_c);
+        br.flush();
+    });
+}
+
+#[test]
+fn renderers_interleave_in_one_frame() {
+    // Same picture as `sample_textures`, drawn by two renderers in turns.
+    render_many("sample_textures", 2, |renderers| {
+        let texture_a = load_texture(0);
+        let texture_b = load_texture(1);
+        let texture_c = load_texture(2);
+
+        renderers[0].push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), &texture_a);
+        renderers[1].push_square_texture(vec2(0.4, 0.0), vec2(0.2, 0.2), &texture_b);
+        renderers[0].push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), &texture_a);
+        renderers[1].flush();
+        renderers[0].flush();
+        renderers[1].push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), &texture_b);
+        renderers[0].push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), &texture_c);
+        renderers[1].flush();
+        renderers[0].flush();
+    });
+}
+
+fn transformed_sprites() -> Vec<Sprite> {
+    vec![
+        Sprite::new(vec2(-0.5, -0.5), vec2(0.4, 0.4)).with_origin(vec2(0.5, 0.5)).with_rotation(0.5),
+        Sprite::new(vec2(0.5, -0.5), vec2(0.4, 0.4)).with_color([255, 64, 64, 255]).with_flip(true, false),
+        Sprite::new(vec2(-0.5, 0.5), vec2(0.2, 0.2)).with_origin(vec2(0.5, 0.5)).with_scale(vec2(2., 1.)).with_flip(false, true),
+        Sprite::new(vec2(0.3, 0.3), vec2(0.4, 0.4)).with_source(Rect::new(vec2(0.25, 0.25), vec2(0.5, 0.5))).with_color([255, 255, 255, 128]),
+    ]
+}
+
+#[test]
+fn sprites() {
+    render("sprites", |br| {
+        let texture = load_texture(1);
+
+        for sprite in &transformed_sprites() {
+            br.draw_sprite(sprite, &texture);
+        }
+        br.flush();
+    });
+}
+
+#[test]
+fn instanced_sprites() {
+    render_instanced("sprites", |ir| {
+        let texture = load_texture(1);
+
+        for sprite in &transformed_sprites() {
+            ir.draw_sprite(sprite, &texture);
+        }
+        ir.flush();
+    });
+}
+
+#[test]
+fn atlas_regions() {
+    render("atlas_regions", |br| {
+        let mut builder = AtlasBuilder::new(2048);
+        for i in 0..3 {
+            builder.try_add_file(format!("sample_texture_{}", i), Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("sample_texture_{}.png", i))).unwrap();
+        }
+        let atlas = builder.try_build().unwrap();
+        assert_eq!(atlas.pages.len(), 1);
+
+        for (i, name) in ["sample_texture_0", "sample_texture_1", "sample_texture_2"].iter().enumerate() {
+            let region = atlas.region(name).unwrap();
+            let sprite = Sprite::new(vec2(i as f32 * 0.6 - 0.9, -0.3), vec2(0.5, 0.6)).with_source(region.rect);
+            br.draw_sprite(&sprite, atlas.page(region));
+        }
+        br.flush();
+    });
+}
+
+#[test]
+fn bundled_atlas_and_shaders() {
+    // Same picture as `atlas_regions`, from a bundle.
+    let mut builder = AtlasBuilder::new(2048);
+    for i in 0..3 {
+        builder.try_add_file(format!("sample_texture_{}", i), Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("sample_texture_{}.png", i))).unwrap();
+    }
+    let preprocessor = Preprocessor::new().with_texture_slots(BUNDLE_TEXTURE_SLOTS);
+    let shaders = ["simple2d.vert", "simple2d.frag"].iter().map(|name| {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders").join(name);
+        (name.to_string(), preprocessor.preprocess_file(path).unwrap().source)
+    }).collect();
+    let mut bytes = Vec::new();
+    PackedBundle { atlas: builder.pack().unwrap(), shaders }.write(&mut bytes).unwrap();
+
+    check_frame("atlas_regions", || {
+        let bundle = Bundle::try_load(&bytes[..]).unwrap();
+        let mut br = BatchRenderer::try_from_preprocessed(bundle.shader("simple2d.frag").unwrap(), bundle.shader("simple2d.vert").unwrap()).unwrap();
+        br.bind();
+
+        for (i, name) in ["sample_texture_0", "sample_texture_1", "sample_texture_2"].iter().enumerate() {
+            let region = bundle.atlas.region(name).unwrap();
+            let sprite = Sprite::new(vec2(i as f32 * 0.6 - 0.9, -0.3), vec2(0.5, 0.6)).with_source(region.rect);
+            br.draw_sprite(&sprite, bundle.atlas.page(region));
+        }
+        br.flush();
+    });
+}
+
+#[test]
+fn generated_textures() {
+    render("generated_textures", |br| {
+        // Odd sizes, so rows are not 4-byte aligned.
+        let checker: Vec<u8> = (0..7 * 5).flat_map(|i| if (i % 7 + i / 7) % 2 == 0 { [255, 255, 0, 255] } else { [0, 0, 255, 255] }).collect();
+        let checker = Texture::try_from_rgba(7, 5, &checker).unwrap();
+        let mask: Vec<u8> = (0..33 * 33).map(|i| {
+            let (x, y) = ((i % 33) as f32 - 16., (i / 33) as f32 - 16.);
+            if x * x + y * y < 256. { 255 } else { 0 }
+        }).collect();
+        let mask = Texture::try_from_r8(33, 33, &mask).unwrap();
+
+        br.push_square_texture(vec2(-0.9, -0.5), vec2(0.8, 1.), &checker);
+        br.draw_sprite(&Sprite::new(vec2(0.1, -0.5), vec2(0.8, 1.)).with_color([64, 255, 64, 255]), &mask);
+        br.flush();
+    });
+}
+
+#[test]
+fn updated_region() {
+    render("updated_region", |br| {
+        let red: Vec<u8> = [255, 0, 0, 255].iter().cycle().take(4 * 4 * 4).cloned().collect();
+        let mut texture = Texture::try_from_rgba(4, 4, &red).unwrap();
+        texture.set_options(&TextureOptions::pixel_art()).unwrap();
+
+        // Bottom-right quarter, then a single pixel at the top-left.
+        texture.update_region(2, 0, 2, 2, &[0, 0, 255, 255].repeat(4)).unwrap();
+        texture.update_region(0, 3, 1, 1, &[255, 255, 255, 255]).unwrap();
+
+        br.push_square_texture(vec2(-0.5, -0.5), vec2(1., 1.), &texture);
+        br.flush();
+    });
+}
+
+#[test]
+fn texture_options() {
+    render("texture_options", |br| {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255];
+        let options = [
+            TextureOptions::pixel_art(),
+            TextureOptions::pixel_art().with_wrap(Wrap::Repeat),
+            TextureOptions::pixel_art().with_wrap(Wrap::Mirror),
+            TextureOptions::pixel_art().with_wrap(Wrap::Border).with_border_color([1., 1., 0., 1.]),
+            TextureOptions::new().with_filter(Filter::Linear).with_wrap(Wrap::Clamp),
+        ];
+        let textures: Vec<Texture> = options.iter().map(|options| {
+            let mut texture = Texture::try_from_rgba(2, 2, &pixels).unwrap();
+            texture.set_options(options).unwrap();
+            texture
+        }).collect();
+
+        // Three repetitions of the texture in each direction.
+        let source = Rect::new(vec2(-1., -1.), vec2(3., 3.));
+        for (i, texture) in textures.iter().enumerate() {
+            let sprite = Sprite::new(vec2(i as f32 * 0.38 - 0.95, -0.3), vec2(0.34, 0.6)).with_source(source);
+            br.draw_sprite(&sprite, texture);
+        }
+        br.flush();
+    });
+}
+
+#[test]
+fn streaming_strategies() {
+    let strategies = [StreamingStrategy::Orphan, StreamingStrategy::SubData, StreamingStrategy::Ring { regions: 2 }];
+
+    for &strategy in &strategies {
+        // Same picture as `sample_textures`, flushed in parts so that the
+        // ring wraps around.
+        render("sample_textures", |br| {
+            br.set_streaming(strategy).unwrap();
+            let texture_a = load_texture(0);
+            let texture_b = load_texture(1);
+            let texture_c = load_texture(2);
+
+            br.push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), &texture_a);
+            br.push_square_texture(vec2(0.4, 0.0), vec2(0.2, 0.2), &texture_b);
+            br.flush();
+            br.push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), &texture_a);
+            br.flush();
+            br.push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), &texture_b);
+            br.push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), &texture_c);
+            br.flush();
+        });
+    }
+}
+
+#[test]
+fn instanced_sample_textures() {
+    // Same picture as `sample_textures`, one instance per square.
+    render_instanced("sample_textures", |ir| {
+        let texture_a = load_texture(0);
+        let texture_b = load_texture(1);
+        let texture_c = load_texture(2);
+
+        ir.push_square_texture(vec2(-0.4, -0.4), vec2(0.2, 0.2), &texture_a);
+        ir.push_square_texture(vec2(0.4, 0.0), vec2(0.2, 0.2), &texture_b);
+        ir.push_square_texture(vec2(-0.4, 0.4), vec2(0.2, 0.2), &texture_a);
+        ir.push_square_texture(vec2(0.4, 0.4), vec2(0.2, 0.2), &texture_b);
+        ir.push_square_texture(vec2(0.0, 0.0), vec2(0.2, 0.2), &texture_c);
+        ir.flush();
+    });
+}
+
+#[test]
+fn overlapping_squares_keep_push_order() {
+    render("overlapping_squares_keep_push_order", |br| {
+        let texture_a = load_texture(0);
+        let texture_c = load_texture(2);
+
+        br.push_square_texture(vec2(-0.5, -0.5), vec2(0.8, 0.8), &texture_c);
+        br.push_square_texture(vec2(-0.2, -0.2), vec2(0.8, 0.8), &texture_a);
+        br.flush();
+    });
+}
+
+#[test]
+fn more_textures_than_slots() {
+    render("more_textures_than_slots", |br| {
+        let textures: Vec<Texture> = (0..20).map(|i| load_texture(i % 3)).collect();
+
+        for (i, texture) in textures.iter().enumerate() {
+            let x = (i % 5) as f32 * 0.4 - 1.0;
+            let y = (i / 5) as f32 * 0.5 - 1.0;
+            br.push_square_texture(vec2(x, y), vec2(0.35, 0.45), texture);
+        }
+        br.flush();
+    });
+}
+
+#[test]
+fn framebuffer_upscale() {
+    render("framebuffer_upscale", |br| {
+        let texture = load_texture(1);
+        let mut target = Framebuffer::try_new(50, 38, false).unwrap();
+
+        target.render(|| {
+            target.clear([0.1, 0.3, 0.2, 1.]);
+            for sprite in &transformed_sprites() {
+                br.draw_sprite(sprite, &texture);
+            }
+            br.flush();
+        });
+
+        target.color.set_options(&TextureOptions::pixel_art()).unwrap();
+        br.push_square_texture(vec2(-1., -1.), vec2(2., 2.), &target.color);
+        br.flush();
+    });
+}
+
+#[test]
+fn depthless_target_keeps_push_order() {
+    render("depthless_target_keeps_push_order", |br| {
+        let red = Texture::try_from_rgba(1, 1, &[220, 40, 40, 255]).unwrap();
+        let blue = Texture::try_from_rgba(1, 1, &[40, 60, 220, 255]).unwrap();
+        assert!(red.handler < blue.handler);
+        let target = Framebuffer::try_new(200, 150, false).unwrap();
+
+        // Sorted by texture without a depth buffer, blue would end up on top.
+        target.render(|| {
+            target.clear([0.1, 0.3, 0.2, 1.]);
+            br.push_square_texture(vec2(-0.6, -0.6), vec2(0.9, 0.9), &blue);
+            br.push_square_texture(vec2(-0.2, -0.2), vec2(0.9, 0.9), &red);
+            br.flush();
+        });
+
+        br.push_square_texture(vec2(-1., -1.), vec2(2., 2.), &target.color);
+        br.flush();
+    });
+}
+
+#[test]
+fn post_effects() {
+    render("post_effects", |br| {
+        let texture_a = load_texture(0);
+        let texture_c = load_texture(2);
+
+        // Warm grade: red lifted, blue lowered.
+        let mut lut = identity_lut(16).unwrap();
+        for pixel in lut.pixels_mut() {
+            pixel.0[0] = pixel.0[0].saturating_add(40);
+            pixel.0[2] = pixel.0[2].saturating_sub(40);
+        }
+        let options = TextureOptions::new().with_filter(Filter::Linear).with_wrap(Wrap::Clamp).with_mipmaps(false);
+        let lut = Texture::try_from_image_with(&image::DynamicImage::ImageRgba8(lut), &options).unwrap();
+
+        let mut post = PostProcessor::try_new(200, 150).unwrap()
+            .with_effect(PostEffect::try_bloom(0.6, 1.5).unwrap())
+            .with_effect(PostEffect::try_color_grade(lut, 1.).unwrap())
+            .with_effect(PostEffect::try_vignette(0.4, 0.8).unwrap())
+            .with_effect(PostEffect::try_crt(4., 0.3).unwrap());
+
+        post.capture(|| {
+            post.source().clear([0.1, 0.1, 0.2, 1.]);
+            br.push_square_texture(vec2(-0.8, -0.6), vec2(0.7, 0.9), &texture_a);
+            br.push_square_texture(vec2(0.1, -0.4), vec2(0.6, 0.8), &texture_c);
+            br.flush();
+        });
+        post.apply();
+    });
+}
+
+#[test]
+fn camera() {
+    render("camera", |br| {
+        let texture = load_texture(1);
+        let camera = Camera2D::new(vec2(200., 150.)).with_position(vec2(1., 0.5)).with_zoom(40.).with_rotation(0.3);
+        br.set_camera(&camera);
+
+        // Unit squares stay square on the 4:3 frame.
+        for x in -2..4 {
+            for y in -1..3 {
+                if (x + y) % 2 == 0 {
+                    br.push_square_texture(vec2(x as f32, y as f32), vec2(1., 1.), &texture);
+                }
+            }
+        }
+        br.push_square(camera.screen_to_world(vec2(20., 20.)), vec2(0.25, 0.25));
+        br.flush();
+    });
+}
+
+#[test]
+fn virtual_resolution() {
+    render("virtual_resolution", |br| {
+        let texture = load_texture(2);
+        // Scaled 3 times onto the 200x150 frame, bars around it.
+        let screen = VirtualScreen::try_new(VirtualResolution::new(60, 40, Scaling::PixelPerfect)).unwrap();
+        br.set_camera(&screen.resolution.camera());
+
+        screen.render(|| {
+            screen.target().clear([0.2, 0.4, 0.8, 1.]);
+            br.push_square_texture(vec2(5., 5.), vec2(30., 30.), &texture);
+            br.draw_sprite(&Sprite::new(vec2(45., 20.), vec2(12., 12.)).with_origin(vec2(0.5, 0.5)).with_rotation(0.4), &texture);
+            br.flush();
+        });
+        screen.present(200, 150);
+    });
+}
+
+#[test]
+fn layers_and_depth() {
+    render("layers_and_depth", |br| {
+        let solid = |color: [u8; 4]| Texture::try_from_rgba(1, 1, &color).unwrap();
+        let red = solid([220, 40, 40, 255]);
+        let green = solid([40, 200, 60, 255]);
+        let blue = solid([40, 60, 220, 255]);
+        let white = solid([255, 255, 255, 255]);
+
+        // Pushed in the reverse of the order they should appear in.
+        br.draw_sprite(&Sprite::new(vec2(0.3, 0.1), vec2(0.6, 0.6)).with_color([255, 230, 0, 160]).with_layer(2), &white);
+        br.draw_sprite(&Sprite::new(vec2(-0.2, -0.2), vec2(0.6, 0.6)).with_layer(1), &red);
+        br.draw_sprite(&Sprite::new(vec2(-0.9, -0.3), vec2(1.2, 0.4)).with_color([255, 255, 255, 128]).with_depth(1.), &white);
+        br.draw_sprite(&Sprite::new(vec2(-0.6, -0.8), vec2(0.6, 0.9)).with_depth(0.5), &green);
+        br.draw_sprite(&Sprite::new(vec2(-0.8, -0.6), vec2(0.9, 0.9)), &blue);
+        br.flush();
+    });
+}